@@ -0,0 +1,455 @@
+//! SM83 disassembler.
+//!
+//! Instructions are decoded from the opcode bit fields instead of a 512 entry table, the
+//! same way the opcode map is usually laid out: `xx yyy zzz` with `yyy` split in `pp q`.
+//! Operands render in RGBDS syntax and IO registers use the `hardware.inc` names, so
+//! `0xE0 0x40` reads `LDH [rLCDC], A`. Cycle counts are in clock cycles, the unit
+//! [`GameBoy::tick`] returns.
+//!
+//! [`GameBoy::tick`]: crate::gameboy::GameBoy::tick
+
+use core::fmt;
+
+const R8: [&str; 8] = ["B", "C", "D", "E", "H", "L", "[HL]", "A"];
+const R16: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const R16_STACK: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const R16_MEMORY: [&str; 4] = ["[BC]", "[DE]", "[HL+]", "[HL-]"];
+const CONDITIONS: [&str; 4] = ["NZ", "Z", "NC", "C"];
+const ALU: [&str; 8] = ["ADD", "ADC", "SUB", "SBC", "AND", "XOR", "OR", "CP"];
+const ACCUMULATOR_OPS: [&str; 8] = ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"];
+const ROTATIONS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// A register, or a register used as a pointer such as `[HL+]`.
+    Register(&'static str),
+    Condition(&'static str),
+    Immediate8(u8),
+    Immediate16(u16),
+    /// Absolute memory operand, `[$C000]`.
+    Address(u16),
+    /// LDH operand in the 0xFF00 page.
+    HighAddress(u8),
+    /// Jump target of a relative jump, already resolved.
+    Relative(u16),
+    /// Signed offset added to SP.
+    StackOffset(i8),
+    Bit(u8),
+    Vector(u8),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Operand::Register(name) | Operand::Condition(name) => write!(f, "{}", name),
+            Operand::Immediate8(value) => write!(f, "${:02X}", value),
+            Operand::Immediate16(value) | Operand::Relative(value) => write!(f, "${:04X}", value),
+            Operand::Address(address) => match io_register_name(address) {
+                Some(name) => write!(f, "[{}]", name),
+                None => write!(f, "[${:04X}]", address),
+            },
+            Operand::HighAddress(offset) => match io_register_name(0xFF00 | offset as u16) {
+                Some(name) => write!(f, "[{}]", name),
+                None => write!(f, "[$FF{:02X}]", offset),
+            },
+            Operand::StackOffset(offset) if offset < 0 => {
+                write!(f, "SP-${:02X}", offset.unsigned_abs())
+            }
+            Operand::StackOffset(offset) => write!(f, "SP+${:02X}", offset),
+            Operand::Bit(bit) => write!(f, "{}", bit),
+            Operand::Vector(vector) => write!(f, "${:02X}", vector),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u16,
+    /// Raw bytes, only the first `length` are meaningful.
+    pub bytes: [u8; 3],
+    pub length: u8,
+    pub mnemonic: &'static str,
+    pub operands: [Option<Operand>; 2],
+    /// Cycles taken, or the cycles of the not taken path for conditional branches.
+    pub cycles: u8,
+    /// Cycles of a conditional branch when it is taken.
+    pub branch_cycles: Option<u8>,
+}
+
+impl Instruction {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.length as usize]
+    }
+
+    /// Address of the instruction that follows.
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.length as u16)
+    }
+
+    fn new(address: u16, bytes: [u8; 3], mnemonic: &'static str) -> Self {
+        Self {
+            address,
+            bytes,
+            length: 1,
+            mnemonic,
+            operands: [None, None],
+            cycles: 4,
+            branch_cycles: None,
+        }
+    }
+
+    fn length(mut self, length: u8) -> Self {
+        self.length = length;
+        self
+    }
+
+    fn cycles(mut self, cycles: u8) -> Self {
+        self.cycles = cycles;
+        self
+    }
+
+    fn branch(mut self, taken: u8) -> Self {
+        self.branch_cycles = Some(taken);
+        self
+    }
+
+    fn operands(mut self, first: Operand, second: Operand) -> Self {
+        self.operands = [Some(first), Some(second)];
+        self
+    }
+
+    fn operand(mut self, operand: Operand) -> Self {
+        self.operands = [Some(operand), None];
+        self
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mnemonic)?;
+        match self.operands {
+            [Some(first), Some(second)] => write!(f, " {}, {}", first, second),
+            [Some(first), None] => write!(f, " {}", first),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Decodes the instruction starting at `bytes[0]`, missing trailing bytes read as zero.
+pub fn disassemble(bytes: &[u8], address: u16) -> Instruction {
+    disassemble_with(address, |offset| {
+        bytes
+            .get(offset.wrapping_sub(address) as usize)
+            .copied()
+            .unwrap_or(0)
+    })
+}
+
+/// Decodes the instruction at `address`, fetching bytes through `read`.
+pub fn disassemble_with<F: FnMut(u16) -> u8>(address: u16, mut read: F) -> Instruction {
+    let bytes = [
+        read(address),
+        read(address.wrapping_add(1)),
+        read(address.wrapping_add(2)),
+    ];
+    let opcode = bytes[0];
+    let d8 = bytes[1];
+    let d16 = u16::from_le_bytes([bytes[1], bytes[2]]);
+    let relative = address.wrapping_add(2).wrapping_add(d8 as i8 as u16);
+
+    let x = opcode >> 6;
+    let y = (opcode >> 3) & 0x07;
+    let z = opcode & 0x07;
+    let p = (y >> 1) as usize;
+    let q = y & 0x01;
+    let r8 = |index: u8| Operand::Register(R8[index as usize]);
+    // Accessing [HL] costs one more memory cycle
+    let hl_cycles = |index: u8, cycles: u8, hl: u8| if index == 6 { hl } else { cycles };
+    let op = |mnemonic| Instruction::new(address, bytes, mnemonic);
+
+    use Operand::*;
+    match (x, z) {
+        (0, 0) => match y {
+            0 => op("NOP"),
+            1 => op("LD")
+                .operands(Address(d16), Register("SP"))
+                .length(3)
+                .cycles(20),
+            2 => op("STOP").length(2),
+            3 => op("JR").operand(Relative(relative)).length(2).cycles(12),
+            _ => op("JR")
+                .operands(Condition(CONDITIONS[y as usize - 4]), Relative(relative))
+                .length(2)
+                .cycles(8)
+                .branch(12),
+        },
+        (0, 1) if q == 0 => op("LD")
+            .operands(Register(R16[p]), Immediate16(d16))
+            .length(3)
+            .cycles(12),
+        (0, 1) => op("ADD")
+            .operands(Register("HL"), Register(R16[p]))
+            .cycles(8),
+        (0, 2) if q == 0 => op("LD")
+            .operands(Register(R16_MEMORY[p]), Register("A"))
+            .cycles(8),
+        (0, 2) => op("LD")
+            .operands(Register("A"), Register(R16_MEMORY[p]))
+            .cycles(8),
+        (0, 3) => op(if q == 0 { "INC" } else { "DEC" })
+            .operand(Register(R16[p]))
+            .cycles(8),
+        (0, 4) => op("INC").operand(r8(y)).cycles(hl_cycles(y, 4, 12)),
+        (0, 5) => op("DEC").operand(r8(y)).cycles(hl_cycles(y, 4, 12)),
+        (0, 6) => op("LD")
+            .operands(r8(y), Immediate8(d8))
+            .length(2)
+            .cycles(hl_cycles(y, 8, 12)),
+        (0, _) => op(ACCUMULATOR_OPS[y as usize]),
+
+        (1, 6) if y == 6 => op("HALT"),
+        (1, _) => op("LD")
+            .operands(r8(y), r8(z))
+            .cycles(if y == 6 || z == 6 { 8 } else { 4 }),
+
+        (2, _) => alu(op(ALU[y as usize]), y, r8(z)).cycles(hl_cycles(z, 4, 8)),
+
+        (3, 0) => match y {
+            0..=3 => op("RET")
+                .operand(Condition(CONDITIONS[y as usize]))
+                .cycles(8)
+                .branch(20),
+            4 => op("LDH")
+                .operands(HighAddress(d8), Register("A"))
+                .length(2)
+                .cycles(12),
+            5 => op("ADD")
+                .operands(Register("SP"), Immediate8(d8))
+                .length(2)
+                .cycles(16),
+            6 => op("LDH")
+                .operands(Register("A"), HighAddress(d8))
+                .length(2)
+                .cycles(12),
+            _ => op("LD")
+                .operands(Register("HL"), StackOffset(d8 as i8))
+                .length(2)
+                .cycles(12),
+        },
+        (3, 1) if q == 0 => op("POP").operand(Register(R16_STACK[p])).cycles(12),
+        (3, 1) => match p {
+            0 => op("RET").cycles(16),
+            1 => op("RETI").cycles(16),
+            2 => op("JP").operand(Register("HL")),
+            _ => op("LD").operands(Register("SP"), Register("HL")).cycles(8),
+        },
+        (3, 2) => match y {
+            0..=3 => op("JP")
+                .operands(Condition(CONDITIONS[y as usize]), Immediate16(d16))
+                .length(3)
+                .cycles(12)
+                .branch(16),
+            4 => op("LDH").operands(Register("[C]"), Register("A")).cycles(8),
+            5 => op("LD")
+                .operands(Address(d16), Register("A"))
+                .length(3)
+                .cycles(16),
+            6 => op("LDH").operands(Register("A"), Register("[C]")).cycles(8),
+            _ => op("LD")
+                .operands(Register("A"), Address(d16))
+                .length(3)
+                .cycles(16),
+        },
+        (3, 3) => match y {
+            0 => op("JP").operand(Immediate16(d16)).length(3).cycles(16),
+            1 => prefixed(address, bytes),
+            6 => op("DI"),
+            7 => op("EI"),
+            _ => invalid(op("DB"), opcode),
+        },
+        (3, 4) if y < 4 => op("CALL")
+            .operands(Condition(CONDITIONS[y as usize]), Immediate16(d16))
+            .length(3)
+            .cycles(12)
+            .branch(24),
+        (3, 5) if q == 0 => op("PUSH").operand(Register(R16_STACK[p])).cycles(16),
+        (3, 5) if p == 0 => op("CALL").operand(Immediate16(d16)).length(3).cycles(24),
+        (3, 6) => alu(op(ALU[y as usize]), y, Immediate8(d8))
+            .length(2)
+            .cycles(8),
+        (3, 7) => op("RST").operand(Vector(y * 8)).cycles(16),
+        _ => invalid(op("DB"), opcode),
+    }
+}
+
+/// ADD, ADC and SBC name the accumulator, the other operations leave it implicit.
+fn alu(instruction: Instruction, operation: u8, operand: Operand) -> Instruction {
+    match operation {
+        0 | 1 | 3 => instruction.operands(Operand::Register("A"), operand),
+        _ => instruction.operand(operand),
+    }
+}
+
+/// Opcodes the CPU locks up on, rendered as data.
+fn invalid(instruction: Instruction, opcode: u8) -> Instruction {
+    instruction.operand(Operand::Immediate8(opcode))
+}
+
+fn prefixed(address: u16, bytes: [u8; 3]) -> Instruction {
+    let opcode = bytes[1];
+    let x = opcode >> 6;
+    let y = (opcode >> 3) & 0x07;
+    let z = opcode & 0x07;
+    let register = Operand::Register(R8[z as usize]);
+    let (mnemonic, cycles_hl) = match x {
+        0 => (ROTATIONS[y as usize], 16),
+        1 => ("BIT", 12),
+        2 => ("RES", 16),
+        _ => ("SET", 16),
+    };
+    let instruction = Instruction::new(address, bytes, mnemonic)
+        .length(2)
+        .cycles(if z == 6 { cycles_hl } else { 8 });
+    if x == 0 {
+        instruction.operand(register)
+    } else {
+        instruction.operands(Operand::Bit(y), register)
+    }
+}
+
+/// `hardware.inc` name of an IO register.
+pub fn io_register_name(address: u16) -> Option<&'static str> {
+    let name = match address {
+        0xFF00 => "rP1",
+        0xFF01 => "rSB",
+        0xFF02 => "rSC",
+        0xFF04 => "rDIV",
+        0xFF05 => "rTIMA",
+        0xFF06 => "rTMA",
+        0xFF07 => "rTAC",
+        0xFF0F => "rIF",
+        0xFF10 => "rNR10",
+        0xFF11 => "rNR11",
+        0xFF12 => "rNR12",
+        0xFF13 => "rNR13",
+        0xFF14 => "rNR14",
+        0xFF16 => "rNR21",
+        0xFF17 => "rNR22",
+        0xFF18 => "rNR23",
+        0xFF19 => "rNR24",
+        0xFF1A => "rNR30",
+        0xFF1B => "rNR31",
+        0xFF1C => "rNR32",
+        0xFF1D => "rNR33",
+        0xFF1E => "rNR34",
+        0xFF20 => "rNR41",
+        0xFF21 => "rNR42",
+        0xFF22 => "rNR43",
+        0xFF23 => "rNR44",
+        0xFF24 => "rNR50",
+        0xFF25 => "rNR51",
+        0xFF26 => "rNR52",
+        0xFF40 => "rLCDC",
+        0xFF41 => "rSTAT",
+        0xFF42 => "rSCY",
+        0xFF43 => "rSCX",
+        0xFF44 => "rLY",
+        0xFF45 => "rLYC",
+        0xFF46 => "rDMA",
+        0xFF47 => "rBGP",
+        0xFF48 => "rOBP0",
+        0xFF49 => "rOBP1",
+        0xFF4A => "rWY",
+        0xFF4B => "rWX",
+        0xFF4D => "rKEY1",
+        0xFF4F => "rVBK",
+        0xFF51 => "rHDMA1",
+        0xFF52 => "rHDMA2",
+        0xFF53 => "rHDMA3",
+        0xFF54 => "rHDMA4",
+        0xFF55 => "rHDMA5",
+        0xFF56 => "rRP",
+        0xFF68 => "rBCPS",
+        0xFF69 => "rBCPD",
+        0xFF6A => "rOCPS",
+        0xFF6B => "rOCPD",
+        0xFF70 => "rSVBK",
+        0xFFFF => "rIE",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Address, bytes, text, cycles and cycles when the branch is taken.
+    type Row = (u16, &'static [u8], &'static str, u8, Option<u8>);
+
+    #[test]
+    fn decodes_the_opcode_table() {
+        let table: [Row; 29] = [
+            (0x0100, &[0x00], "NOP", 4, None),
+            (0x0100, &[0xCB, 0x7C], "BIT 7, H", 8, None),
+            (0x0100, &[0xCB, 0x46], "BIT 0, [HL]", 12, None),
+            (0x0100, &[0xCB, 0x37], "SWAP A", 8, None),
+            (0x0100, &[0xCB, 0x86], "RES 0, [HL]", 16, None),
+            (0x0100, &[0xCB, 0xFE], "SET 7, [HL]", 16, None),
+            (0x0100, &[0x3E, 0x42], "LD A, $42", 8, None),
+            (0x0100, &[0x36, 0x42], "LD [HL], $42", 12, None),
+            (0x0100, &[0xC6, 0x10], "ADD A, $10", 8, None),
+            (0x0100, &[0xFE, 0x10], "CP $10", 8, None),
+            (0x0100, &[0x01, 0x34, 0x12], "LD BC, $1234", 12, None),
+            (0x0100, &[0xEA, 0x00, 0xC0], "LD [$C000], A", 16, None),
+            (0x0100, &[0xFA, 0x40, 0xFF], "LD A, [rLCDC]", 16, None),
+            (0x0100, &[0x08, 0x00, 0xC0], "LD [$C000], SP", 20, None),
+            (0x0100, &[0xC3, 0x50, 0x01], "JP $0150", 16, None),
+            (0x0100, &[0xCD, 0x00, 0x40], "CALL $4000", 24, None),
+            (0x0100, &[0xC4, 0x00, 0x40], "CALL NZ, $4000", 12, Some(24)),
+            (0x0150, &[0x18, 0xFE], "JR $0150", 12, None),
+            (0x0150, &[0x20, 0x05], "JR NZ, $0157", 8, Some(12)),
+            (0x0150, &[0x38, 0x80], "JR C, $00D2", 8, Some(12)),
+            (0x0100, &[0xE0, 0x40], "LDH [rLCDC], A", 12, None),
+            (0x0100, &[0xF0, 0x44], "LDH A, [rLY]", 12, None),
+            (0x0100, &[0xE0, 0x80], "LDH [$FF80], A", 12, None),
+            (0x0100, &[0xE2], "LDH [C], A", 8, None),
+            (0x0100, &[0xF8, 0xFE], "LD HL, SP-$02", 12, None),
+            (0x0100, &[0xE8, 0x05], "ADD SP, $05", 16, None),
+            (0x0100, &[0xC0], "RET NZ", 8, Some(20)),
+            (0x0100, &[0xD3], "DB $D3", 4, None),
+            (0x0100, &[0xFF], "RST $38", 16, None),
+        ];
+        for (address, bytes, text, cycles, branch_cycles) in table {
+            let instruction = disassemble(bytes, address);
+            assert_eq!(instruction.to_string(), text);
+            assert_eq!(instruction.bytes(), bytes, "{}", text);
+            assert_eq!(instruction.cycles, cycles, "{}", text);
+            assert_eq!(instruction.branch_cycles, branch_cycles, "{}", text);
+            assert_eq!(
+                instruction.next_address(),
+                address + bytes.len() as u16,
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn wraps_around_the_address_space() {
+        let memory = |address: u16| match address {
+            0xFFFE => 0xC3,
+            0xFFFF => 0x34,
+            _ => 0x12,
+        };
+        let instruction = disassemble_with(0xFFFE, memory);
+        assert_eq!(instruction.to_string(), "JP $1234");
+        assert_eq!(instruction.next_address(), 0x0001);
+
+        // JR backwards from the start of memory
+        let instruction = disassemble(&[0x18, 0xFC], 0x0000);
+        assert_eq!(instruction.to_string(), "JR $FFFE");
+        // Missing operand bytes read as zero
+        assert_eq!(disassemble(&[0x3E], 0x0100).to_string(), "LD A, $00");
+    }
+}
@@ -8,6 +8,7 @@ pub mod flags;
 
 pub mod address;
 pub mod alu;
+pub mod disassembler;
 pub mod opcodes;
 pub mod registers;
 
@@ -37,6 +38,8 @@ pub trait Interface {
     fn any_enabled(&self) -> bool;
     fn set_byte(&mut self, address: u16, value: u8);
     fn get_byte(&mut self, address: u16) -> u8;
+    /// Reads like `get_byte` without side effects, for tools inspecting memory.
+    fn peek_byte(&self, address: u16) -> u8;
 
     fn gpu_screen_on(&self) -> bool;
     fn scan_line(&self) -> u8;
@@ -53,12 +56,9 @@ impl<T: Interface> Cpu<T> {
     }
 
     pub fn new_from_state(interface: T, state: CpuState) -> Self {
-        Cpu {
-            registers: state.registers,
-            interface,
-            tick_count: 0,
-            current_screen_state: false,
-        }
+        let mut cpu = Cpu::new(interface);
+        cpu.load_state(state);
+        cpu
     }
 
     pub fn create_state(&self) -> CpuState {
@@ -66,6 +66,10 @@ impl<T: Interface> Cpu<T> {
             registers: self.registers,
         }
     }
+
+    pub fn load_state(&mut self, state: CpuState) {
+        self.registers = state.registers;
+    }
 }
 
 impl<T: Interface> Cpu<T> {
@@ -0,0 +1,583 @@
+//! Breakpoints, watchpoints and stepping on top of [`GameBoy::tick`].
+//!
+//! Frontends configure a [`Debugger`] through [`GameBoy::debugger`] and drive the emulator
+//! with the stepping commands, each returns the [`DebugEvent`] that ended it. Breakpoints
+//! and execute watchpoints stop before the instruction runs, read and write watchpoints
+//! stop after the instruction that made the access.
+
+use crate::cpu::registers::Registers;
+use crate::cpu::Step;
+use crate::gameboy::{GameBoy, SCREEN_HEIGHT};
+use crate::hardware::observer::BusObserver;
+use crate::hardware::Screen;
+use bitflags::bitflags;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+// One frame of the DMG, commands give up after it so code that never gets there cannot hang
+const CYCLES_PER_FRAME: u32 = 70224;
+
+bitflags!(
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub struct Access: u8 {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXECUTE = 1 << 2;
+    }
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl Register {
+    fn read(&self, registers: &Registers) -> u16 {
+        match self {
+            Register::A => registers.a as u16,
+            Register::F => registers.flags.read_value() as u16,
+            Register::B => registers.b as u16,
+            Register::C => registers.c as u16,
+            Register::D => registers.d as u16,
+            Register::E => registers.e as u16,
+            Register::H => registers.h as u16,
+            Register::L => registers.l as u16,
+            Register::AF => registers.get_af(),
+            Register::BC => registers.get_bc(),
+            Register::DE => registers.get_de(),
+            Register::HL => registers.get_hl(),
+            Register::SP => registers.sp,
+            Register::PC => registers.pc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Register test a breakpoint must pass to stop, e.g. `A == 0x12`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn new(register: Register, comparison: Comparison, value: u16) -> Self {
+        Self {
+            register,
+            comparison,
+            value,
+        }
+    }
+
+    fn matches(&self, registers: &Registers) -> bool {
+        let current = self.register.read(registers);
+        match self.comparison {
+            Comparison::Equal => current == self.value,
+            Comparison::NotEqual => current != self.value,
+            Comparison::Less => current < self.value,
+            Comparison::LessOrEqual => current <= self.value,
+            Comparison::Greater => current > self.value,
+            Comparison::GreaterOrEqual => current >= self.value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    /// ROM bank the address must be mapped from, ignored outside of ROM.
+    pub bank: Option<u16>,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+}
+
+impl Breakpoint {
+    pub fn new(address: u16) -> Self {
+        Self {
+            address,
+            bank: None,
+            condition: None,
+            enabled: true,
+        }
+    }
+
+    pub fn in_bank(mut self, bank: u16) -> Self {
+        self.bank = Some(bank);
+        self
+    }
+
+    pub fn when(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// First and last address watched, both included.
+    pub start: u16,
+    pub end: u16,
+    pub access: Access,
+    pub enabled: bool,
+}
+
+impl Watchpoint {
+    pub fn new(start: u16, end: u16, access: Access) -> Self {
+        Self {
+            start,
+            end,
+            access,
+            enabled: true,
+        }
+    }
+
+    fn matches(&self, address: u16, access: Access) -> bool {
+        self.enabled && self.access.intersects(access) && (self.start..=self.end).contains(&address)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugEvent {
+    /// The step finished without hitting anything.
+    Stepped,
+    /// The frame finished without hitting anything.
+    FrameCompleted,
+    Breakpoint(usize),
+    Watchpoint {
+        index: usize,
+        address: u16,
+        access: Access,
+        value: u8,
+    },
+}
+
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    // Lets bus accesses skip the lookup when no read or write watchpoint is on
+    watching: bool,
+    hit: Option<DebugEvent>,
+    // Address of the breakpoint the last command stopped on, the next one runs past it
+    resume: Option<u16>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            watching: false,
+            hit: None,
+            resume: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(breakpoint);
+        self.breakpoints.len() - 1
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        if index >= self.breakpoints.len() {
+            return None;
+        }
+        Some(self.breakpoints.remove(index))
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(breakpoint) = self.breakpoints.get_mut(index) {
+            breakpoint.enabled = enabled;
+        }
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        self.watchpoints.push(watchpoint);
+        self.update();
+        self.watchpoints.len() - 1
+    }
+
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        if index >= self.watchpoints.len() {
+            return None;
+        }
+        let watchpoint = self.watchpoints.remove(index);
+        self.update();
+        Some(watchpoint)
+    }
+
+    pub fn set_watchpoint_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(watchpoint) = self.watchpoints.get_mut(index) {
+            watchpoint.enabled = enabled;
+        }
+        self.update();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.update();
+    }
+
+    fn update(&mut self) {
+        self.watching = self.watchpoints.iter().any(|watchpoint| {
+            watchpoint.enabled && watchpoint.access.intersects(Access::READ | Access::WRITE)
+        });
+    }
+
+    #[inline(always)]
+    pub(crate) fn watching(&self) -> bool {
+        self.watching
+    }
+
+    /// Records the first watched access of the current instruction.
+    fn on_access(&mut self, address: u16, value: u8, access: Access) {
+        if !self.watching || self.hit.is_some() {
+            return;
+        }
+        self.hit = self
+            .watchpoints
+            .iter()
+            .position(|watchpoint| watchpoint.matches(address, access))
+            .map(|index| DebugEvent::Watchpoint {
+                index,
+                address,
+                access,
+                value,
+            });
+    }
+}
+
+/// Read and write watchpoints follow the bus like any other observer.
+impl BusObserver for Debugger {
+    fn read(&mut self, address: u16, value: u8, _cycle: u64) {
+        self.on_access(address, value, Access::READ);
+    }
+
+    fn write(&mut self, address: u16, value: u8, _cycle: u64) {
+        self.on_access(address, value, Access::WRITE);
+    }
+
+    fn io_read(&mut self, address: u16, value: u8, _cycle: u64) {
+        self.on_access(address, value, Access::READ);
+    }
+
+    fn io_write(&mut self, address: u16, value: u8, _cycle: u64) {
+        self.on_access(address, value, Access::WRITE);
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What happened during one CPU step run under the debugger.
+struct Tick {
+    cycles: u32,
+    executed: bool,
+    returned: bool,
+    frame: bool,
+}
+
+impl<'a, S: Screen> GameBoy<'a, S> {
+    pub fn debugger(&mut self) -> &mut Debugger {
+        &mut self.cpu.interface.debugger
+    }
+
+    /// Runs the next instruction, an interrupt dispatch or halted cycles before it included.
+    pub fn step_into(&mut self) -> DebugEvent {
+        let mut cycles = 0;
+        self.run_until(|tick| {
+            cycles += tick.cycles;
+            (tick.executed || cycles >= CYCLES_PER_FRAME).then_some(DebugEvent::Stepped)
+        })
+    }
+
+    /// Like [`step_into`](Self::step_into) but runs calls and restarts until they return.
+    pub fn step_over(&mut self) -> DebugEvent {
+        let instruction = self.disassemble(self.cpu.registers.pc);
+        if !matches!(instruction.mnemonic, "CALL" | "RST") {
+            return self.step_into();
+        }
+        let target = instruction.next_address();
+        let stack = self.cpu.registers.sp;
+        let mut cycles = 0;
+        self.run_until_with_registers(|tick, registers| {
+            cycles += tick.cycles;
+            // Recursion lands on the target with a deeper stack, a call not taken is done
+            // right away
+            let done = tick.executed && registers.pc == target && registers.sp >= stack;
+            (done || cycles >= CYCLES_PER_FRAME).then_some(DebugEvent::Stepped)
+        })
+    }
+
+    /// Runs until the current function returns to its caller.
+    pub fn step_out(&mut self) -> DebugEvent {
+        let stack = self.cpu.registers.sp;
+        let mut cycles = 0;
+        self.run_until_with_registers(|tick, registers| {
+            cycles += tick.cycles;
+            let done = tick.returned && registers.sp > stack;
+            (done || cycles >= CYCLES_PER_FRAME).then_some(DebugEvent::Stepped)
+        })
+    }
+
+    /// Runs until the PPU enters the next VBlank.
+    pub fn run_to_frame(&mut self) -> DebugEvent {
+        let mut cycles = 0;
+        self.run_until(|tick| {
+            cycles += tick.cycles;
+            (tick.frame || cycles >= CYCLES_PER_FRAME).then_some(DebugEvent::FrameCompleted)
+        })
+    }
+
+    fn run_until<F: FnMut(&Tick) -> Option<DebugEvent>>(&mut self, mut done: F) -> DebugEvent {
+        self.run_until_with_registers(|tick, _| done(tick))
+    }
+
+    fn run_until_with_registers<F: FnMut(&Tick, &Registers) -> Option<DebugEvent>>(
+        &mut self,
+        mut done: F,
+    ) -> DebugEvent {
+        self.cpu.interface.debugger.hit = None;
+        let mut resume = self.cpu.interface.debugger.resume.take();
+        loop {
+            let tick = match self.debug_tick(resume) {
+                Ok(tick) => tick,
+                Err(event) => return event,
+            };
+            if tick.executed {
+                resume = None;
+            }
+            if let Some(event) = done(&tick, &self.cpu.registers) {
+                return event;
+            }
+        }
+    }
+
+    /// Breakpoints at `resume` are not checked so continuing from one moves past it.
+    fn debug_tick(&mut self, resume: Option<u16>) -> Result<Tick, DebugEvent> {
+        let executed = matches!(self.state, Step::Run | Step::HaltBug);
+        let pc = self.cpu.registers.pc;
+        if executed && resume != Some(pc) {
+            if let Some(event) = self.check_breakpoints(pc) {
+                self.cpu.interface.debugger.resume = Some(pc);
+                return Err(event);
+            }
+        }
+        // RET, RETI and the conditional returns
+        let returned = executed && matches!(self.peek(pc), 0xC9 | 0xD9 | 0xC0 | 0xC8 | 0xD0 | 0xD8);
+        let scanline = self.cpu.interface.gpu.scanline;
+        let cycles = self.tick() as u32;
+        if let Some(event) = self.cpu.interface.debugger.hit.take() {
+            return Err(event);
+        }
+        let frame = scanline != SCREEN_HEIGHT as u8
+            && self.cpu.interface.gpu.scanline == SCREEN_HEIGHT as u8;
+        Ok(Tick {
+            cycles,
+            executed,
+            returned,
+            frame,
+        })
+    }
+
+    fn check_breakpoints(&self, pc: u16) -> Option<DebugEvent> {
+        let debugger = &self.cpu.interface.debugger;
+        let bank = self.cpu.interface.cartridge.rom_bank(pc);
+        let breakpoint = debugger.breakpoints.iter().position(|breakpoint| {
+            breakpoint.enabled
+                && breakpoint.address == pc
+                && (pc >= 0x8000 || breakpoint.bank.is_none_or(|expected| expected == bank))
+                && breakpoint
+                    .condition
+                    .is_none_or(|condition| condition.matches(&self.cpu.registers))
+        });
+        if let Some(index) = breakpoint {
+            return Some(DebugEvent::Breakpoint(index));
+        }
+        debugger
+            .watchpoints
+            .iter()
+            .position(|watchpoint| watchpoint.matches(pc, Access::EXECUTE))
+            .map(|index| DebugEvent::Watchpoint {
+                index,
+                address: pc,
+                access: Access::EXECUTE,
+                value: self.peek(pc),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{gameboy, NullScreen, PROGRAM_START};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const START: u16 = PROGRAM_START as u16;
+
+    /// Runs the entry point jump so the program is next.
+    fn start(program: &[u8]) -> GameBoy<'static, NullScreen> {
+        let mut gameboy = gameboy(program);
+        while gameboy.cpu.registers.pc != START {
+            gameboy.step_into();
+        }
+        gameboy
+    }
+
+    #[test]
+    fn step_over_skips_a_call_not_taken() {
+        // XOR A; CALL NZ,$0200; NOP; JR @
+        let mut gameboy = start(&[0xAF, 0xC4, 0x00, 0x02, 0x00, 0x18, 0xFE]);
+        gameboy.step_into();
+        assert_eq!(gameboy.step_over(), DebugEvent::Stepped);
+        assert_eq!(gameboy.cpu.registers.pc, START + 4);
+    }
+
+    #[test]
+    fn step_over_runs_the_call() {
+        // CALL sub; NOP; JR @; sub: NOP; RET
+        let sub = START + 6;
+        let [low, high] = sub.to_le_bytes();
+        let mut gameboy = start(&[0xCD, low, high, 0x00, 0x18, 0xFE, 0x00, 0xC9]);
+        let stack = gameboy.cpu.registers.sp;
+        assert_eq!(gameboy.step_over(), DebugEvent::Stepped);
+        assert_eq!(gameboy.cpu.registers.pc, START + 3);
+        assert_eq!(gameboy.cpu.registers.sp, stack);
+    }
+
+    #[test]
+    fn step_out_returns_to_the_caller() {
+        // CALL sub; JR @; sub: NOP; NOP; RET
+        let sub = START + 5;
+        let [low, high] = sub.to_le_bytes();
+        let mut gameboy = start(&[0xCD, low, high, 0x18, 0xFE, 0x00, 0x00, 0xC9]);
+        gameboy.step_into();
+        assert_eq!(gameboy.cpu.registers.pc, sub);
+        assert_eq!(gameboy.step_out(), DebugEvent::Stepped);
+        assert_eq!(gameboy.cpu.registers.pc, START + 3);
+    }
+
+    #[test]
+    fn step_out_gives_up_outside_of_a_function() {
+        // JR @
+        let mut gameboy = start(&[0x18, 0xFE]);
+        let cycles = gameboy.cycles();
+        assert_eq!(gameboy.step_out(), DebugEvent::Stepped);
+        assert!(gameboy.cycles() - cycles >= CYCLES_PER_FRAME as u64);
+    }
+
+    #[test]
+    fn continuing_runs_past_the_breakpoint() {
+        // NOP; NOP; JR -4
+        let mut gameboy = start(&[0x00, 0x00, 0x18, 0xFC]);
+        gameboy
+            .debugger()
+            .add_breakpoint(Breakpoint::new(START + 1));
+        assert_eq!(gameboy.run_to_frame(), DebugEvent::Breakpoint(0));
+        let cycles = gameboy.cycles();
+        assert_eq!(gameboy.run_to_frame(), DebugEvent::Breakpoint(0));
+        assert_eq!(gameboy.cpu.registers.pc, START + 1);
+        // Once around the loop
+        assert_eq!(gameboy.cycles() - cycles, 4 + 12 + 4);
+    }
+
+    #[test]
+    fn breakpoint_condition_and_bank() {
+        // INC A; JR -3
+        let mut gameboy = start(&[0x3C, 0x18, 0xFD]);
+        let debugger = gameboy.debugger();
+        debugger.add_breakpoint(Breakpoint::new(START).in_bank(1));
+        debugger.add_breakpoint(Breakpoint::new(START).when(Condition::new(
+            Register::A,
+            Comparison::Equal,
+            0x10,
+        )));
+        assert_eq!(gameboy.run_to_frame(), DebugEvent::Breakpoint(1));
+        assert_eq!(gameboy.cpu.registers.a, 0x10);
+    }
+
+    #[test]
+    fn write_watchpoint_stops_after_the_write() {
+        // LD A,$42; LD ($C000),A; JR @
+        let mut gameboy = start(&[0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x18, 0xFE]);
+        gameboy
+            .debugger()
+            .add_watchpoint(Watchpoint::new(0xC000, 0xC0FF, Access::WRITE));
+        assert_eq!(
+            gameboy.run_to_frame(),
+            DebugEvent::Watchpoint {
+                index: 0,
+                address: 0xC000,
+                access: Access::WRITE,
+                value: 0x42,
+            }
+        );
+        assert_eq!(gameboy.cpu.registers.pc, START + 5);
+    }
+
+    struct Writes(Rc<RefCell<Vec<u16>>>);
+
+    impl BusObserver for Writes {
+        fn write(&mut self, address: u16, _value: u8, _cycle: u64) {
+            self.0.borrow_mut().push(address);
+        }
+    }
+
+    #[test]
+    fn watchpoints_and_observers_skip_cheat_writes() {
+        // LD A,$42; LD ($D000),A; JR @
+        let mut gameboy = start(&[0x3E, 0x42, 0xEA, 0x00, 0xD0, 0x18, 0xFE]);
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        gameboy.attach_observer(Box::new(Writes(writes.clone())));
+        gameboy.cheats().add("019947D3").unwrap();
+        gameboy
+            .debugger()
+            .add_watchpoint(Watchpoint::new(0xD000, 0xDFFF, Access::WRITE));
+        assert!(matches!(
+            gameboy.run_to_frame(),
+            DebugEvent::Watchpoint {
+                address: 0xD000,
+                ..
+            }
+        ));
+        assert_eq!(gameboy.run_to_frame(), DebugEvent::FrameCompleted);
+        assert_eq!(gameboy.peek(0xD347), 0x99);
+        assert_eq!(*writes.borrow(), [0xD000]);
+    }
+}
@@ -1,14 +1,23 @@
 use crate::cpu::address::Cpu;
+use crate::cpu::disassembler::{self, Instruction};
 use crate::cpu::opcodes::DecodeStep;
 use crate::cpu::{CpuState, Interface, Step};
 use crate::hardware::boot_rom::Bootrom;
-use crate::hardware::cartridge::Cartridge;
+use crate::hardware::camera::CameraSensor;
+use crate::hardware::cartridge::{Cartridge, CartridgeState};
+use crate::hardware::cheats::CheatManager;
+use crate::hardware::infrared::InfraredPort;
 use crate::hardware::input::Button;
+use crate::hardware::observer::BusObserver;
 use crate::hardware::ppu::PPuState;
 use crate::hardware::{Hardware, HardwareState, Screen};
+use crate::is_log_enabled;
+use crate::trace::{TraceLine, TraceSink};
 
 #[cfg(not(feature = "std"))]
 use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
 
 pub const SCREEN_HEIGHT: usize = 144;
 pub const SCREEN_WIDTH: usize = 160;
@@ -19,11 +28,13 @@ pub struct GameBoyState {
     pub cpu_state: CpuState,
     pub ppu_state: PPuState,
     pub hard_ware_state: HardwareState,
+    pub cartridge_state: CartridgeState,
     pub state: Step,
 }
 pub struct GameBoy<'a, S: Screen> {
     pub cpu: Cpu<Hardware<'a, S>>,
     pub state: Step,
+    trace: Option<Box<dyn TraceSink + 'a>>,
 }
 
 impl<'a, S: Screen> GameBoy<'a, S> {
@@ -46,6 +57,7 @@ impl<'a, S: Screen> GameBoy<'a, S> {
         GameBoy {
             cpu,
             state: Step::Run,
+            trace: None,
         }
     }
 
@@ -57,34 +69,41 @@ impl<'a, S: Screen> GameBoy<'a, S> {
         state: GameBoyState,
     ) -> GameBoy<S> {
         boot_rom.deactivate();
-        let hardware = Hardware::create_from_state(
-            screen,
-            cartridge,
-            boot_rom,
-            player,
-            state.hard_ware_state,
-            state.ppu_state,
-        );
-        let cpu = Cpu::new_from_state(hardware, state.cpu_state);
-
-        GameBoy {
-            cpu,
-            state: state.state,
-        }
+        let hardware = Hardware::create(screen, cartridge, boot_rom, player);
+        let mut gameboy = GameBoy {
+            cpu: Cpu::new(hardware),
+            state: Step::Run,
+            trace: None,
+        };
+        gameboy.load_state(state);
+        gameboy
     }
 }
 
 impl<'a, S: Screen> GameBoy<'a, S> {
     pub fn tick(&mut self) -> u8 {
+        if matches!(self.state, Step::Run | Step::HaltBug) {
+            if is_log_enabled() {
+                self.trace_instruction();
+            }
+            self.cpu.interface.observe_execute(self.cpu.registers.pc);
+        }
         let (cycles, decode_step) = self.cpu.step(self.state);
+        self.cpu.interface.cycles += cycles as u64;
         if cycles != 0 {
             let interrupts = &mut self.cpu.interface.interrupt_handler;
             interrupts.step();
             self.cpu.interface.input_controller.update_state(interrupts);
             self.cpu.interface.timer.do_cycle(cycles as u32, interrupts);
+            let scanline = self.cpu.interface.gpu.scanline;
             self.cpu.interface.gpu.step(cycles as isize, interrupts);
             self.cpu.interface.sound.do_cycle(cycles as u32);
-            self.cpu.interface.cartridge.step();
+            self.cpu.interface.cartridge.step(cycles);
+            if scanline != SCREEN_HEIGHT as u8
+                && self.cpu.interface.gpu.scanline == SCREEN_HEIGHT as u8
+            {
+                self.cpu.interface.apply_cheats();
+            }
         }
         let next_state = match decode_step {
             DecodeStep::Run => {
@@ -112,15 +131,87 @@ impl<'a, S: Screen> GameBoy<'a, S> {
         cycles
     }
 
+    fn trace_instruction(&mut self) {
+        let Some(sink) = self.trace.as_mut() else {
+            return;
+        };
+        let registers = &self.cpu.registers;
+        let pc = registers.pc;
+        let interface = &self.cpu.interface;
+        sink.trace(&TraceLine {
+            a: registers.a,
+            f: registers.flags.read_value(),
+            b: registers.b,
+            c: registers.c,
+            d: registers.d,
+            e: registers.e,
+            h: registers.h,
+            l: registers.l,
+            sp: registers.sp,
+            pc,
+            pc_mem: core::array::from_fn(|offset| {
+                interface.peek_byte(pc.wrapping_add(offset as u16))
+            }),
+        });
+    }
+
     pub fn create_state(&self) -> GameBoyState {
         GameBoyState {
             cpu_state: self.cpu.create_state(),
             ppu_state: self.cpu.interface.gpu.create_state(),
             hard_ware_state: self.cpu.interface.create_state(),
+            cartridge_state: self.cpu.interface.cartridge.create_state(),
             state: self.state,
         }
     }
 
+    /// Restores a state in place, the screen, cartridge and audio player attached to this
+    /// GameBoy are kept so frontends can swap states at any time.
+    pub fn load_state(&mut self, state: GameBoyState) {
+        self.cpu.load_state(state.cpu_state);
+        self.cpu
+            .interface
+            .load_state(state.hard_ware_state, state.ppu_state);
+        self.cpu
+            .interface
+            .cartridge
+            .load_state(state.cartridge_state);
+        self.state = state.state;
+    }
+
+    /// Reads memory the way the CPU sees it without triggering any side effect.
+    pub fn peek(&self, address: u16) -> u8 {
+        self.cpu.interface.peek_byte(address)
+    }
+
+    /// Writes memory through the bus like the CPU would, MBC registers included.
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.cpu.interface.set_byte(address, value)
+    }
+
+    /// Decodes the instruction at `address` as currently mapped.
+    pub fn disassemble(&self, address: u16) -> Instruction {
+        disassembler::disassemble_with(address, |address| self.peek(address))
+    }
+
+    pub fn cheats(&mut self) -> &mut CheatManager {
+        &mut self.cpu.interface.cheats
+    }
+
+    /// Lets the cartridge clock follow the host time, by default it counts emulated cycles.
+    pub fn set_rtc_host_sync(&mut self, enabled: bool) {
+        self.cpu.interface.cartridge.set_rtc_host_sync(enabled);
+    }
+
+    /// Battery backed cartridge memory in the .sav layout, empty for carts without a battery.
+    pub fn save_ram(&self) -> Vec<u8> {
+        self.cpu.interface.cartridge.save_ram()
+    }
+
+    pub fn load_ram(&mut self, data: &[u8]) {
+        self.cpu.interface.cartridge.load_ram(data);
+    }
+
     pub fn get_screen(&mut self) -> &mut S {
         &mut self.cpu.interface.gpu.screen
     }
@@ -132,6 +223,52 @@ impl<'a, S: Screen> GameBoy<'a, S> {
     pub fn key_released(&mut self, button: Button) {
         self.cpu.interface.input_controller.key_released(button);
     }
+
+    /// Tilts the cartridge accelerometer, each axis is given in g where 1.0 is a full tilt.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.cpu.interface.cartridge.set_tilt(x, y);
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.cpu.interface.cartridge.rumble()
+    }
+
+    pub fn attach_infrared(&mut self, port: Box<dyn InfraredPort>) {
+        self.cpu.interface.cartridge.attach_infrared(port);
+    }
+
+    /// Sends every executed instruction to `sink` while [`crate::is_log_enabled`].
+    pub fn attach_trace(&mut self, sink: Box<dyn TraceSink + 'a>) {
+        self.trace = Some(sink);
+    }
+
+    pub fn detach_trace(&mut self) -> Option<Box<dyn TraceSink + 'a>> {
+        self.trace.take()
+    }
+
+    /// Makes reads of LY return 0x90 as the gameboy-doctor reference logs were recorded,
+    /// so traces can be compared past the first LY poll. The PPU itself is not affected.
+    pub fn set_doctor_ly(&mut self, enabled: bool) {
+        self.cpu.interface.doctor_ly = enabled;
+    }
+
+    /// Reports every bus access to `observer`, see [`crate::hardware::observer`].
+    pub fn attach_observer(&mut self, observer: Box<dyn BusObserver + 'a>) {
+        self.cpu.interface.observer = Some(observer);
+    }
+
+    pub fn detach_observer(&mut self) -> Option<Box<dyn BusObserver + 'a>> {
+        self.cpu.interface.observer.take()
+    }
+
+    /// Cycles emulated since power on, the timestamp given to bus observers.
+    pub fn cycles(&self) -> u64 {
+        self.cpu.interface.cycles
+    }
+
+    pub fn attach_camera(&mut self, sensor: Box<dyn CameraSensor>) {
+        self.cpu.interface.cartridge.attach_camera(sensor);
+    }
 }
 
 pub enum GbEvents {
@@ -39,6 +39,11 @@ impl Bootrom {
     pub fn deactivate(&mut self) {
         self.active = false;
     }
+
+    /// Maps the boot ROM in or out, it stays out when there is no boot ROM data.
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active && self.data.0.len() >= 0x100;
+    }
 }
 
 impl Index<u16> for Bootrom {
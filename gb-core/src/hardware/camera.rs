@@ -0,0 +1,26 @@
+pub const CAMERA_WIDTH: usize = 128;
+pub const CAMERA_HEIGHT: usize = 112;
+pub const CAMERA_PIXELS: usize = CAMERA_WIDTH * CAMERA_HEIGHT;
+
+pub type CameraFrame = [u8; CAMERA_PIXELS];
+
+/// Image sensor of the Pocket Camera.
+pub trait CameraSensor {
+    /// Fills `frame` with a row major 8-bit grayscale picture, 0 is black and 255 white.
+    fn capture(&mut self, frame: &mut CameraFrame);
+}
+
+impl<F: FnMut(&mut CameraFrame)> CameraSensor for F {
+    fn capture(&mut self, frame: &mut CameraFrame) {
+        self(frame)
+    }
+}
+
+/// Sensor with the lens cap on, every capture is a flat mid gray picture.
+pub struct NullCamera;
+
+impl CameraSensor for NullCamera {
+    fn capture(&mut self, frame: &mut CameraFrame) {
+        frame.fill(0x80);
+    }
+}
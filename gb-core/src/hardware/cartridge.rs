@@ -6,16 +6,203 @@ use alloc::vec::{self, Vec};
 #[cfg(not(feature = "std"))]
 use alloc::boxed::Box;
 
-use super::rom::{Rom, RomManager};
+use super::camera::{
+    CameraFrame, CameraSensor, NullCamera, CAMERA_HEIGHT, CAMERA_PIXELS, CAMERA_WIDTH,
+};
+use super::infrared::{InfraredPort, NullInfrared};
+use super::rom::{Rom, RomManager, NINTENDO_LOGO, NINTENDO_LOGO_OFFSET};
 
 pub trait Cartridge {
-    fn step(&mut self) {}
+    fn step(&mut self, _cycles: u8) {}
 
     fn read_rom(&self, address: u16) -> u8;
     fn write_rom(&mut self, address: u16, value: u8);
 
     fn read_ram(&self, address: u16) -> u8;
     fn write_ram(&mut self, address: u16, value: u8);
+
+    /// State of the rumble motor on cartridges that carry one.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// Connects the infrared transceiver of cartridges that carry one.
+    fn attach_infrared(&mut self, _port: Box<dyn InfraredPort>) {}
+
+    /// Feeds the accelerometer of cartridges that carry one, values are in g.
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    /// Connects the image sensor of camera cartridges.
+    fn attach_camera(&mut self, _sensor: Box<dyn CameraSensor>) {}
+
+    /// ROM bank mapped at `address`, debuggers use it to tell apart code in different banks.
+    fn rom_bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Lets the cartridge clock follow the host time instead of emulated cycles.
+    fn set_rtc_host_sync(&mut self, _enabled: bool) {}
+
+    /// Battery backed memory laid out like the .sav files of other emulators, empty when the
+    /// cartridge keeps nothing across power cycles.
+    fn save_ram(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores memory written by `save_ram` or by another emulator.
+    fn load_ram(&mut self, _data: &[u8]) {}
+
+    /// Snapshot of the mapper registers and RAM for save states.
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::None
+    }
+
+    /// Restores a snapshot taken with `create_state`, states of a different mapper are ignored.
+    fn load_state(&mut self, _state: CartridgeState) {}
+}
+
+const RAM_BANK_SIZE: usize = 0xBFFF - 0xA000 + 1;
+const RTC_FOOTER_SIZE: usize = 48;
+
+fn dump_ram(banks: &[[u8; RAM_BANK_SIZE]]) -> Vec<u8> {
+    banks.iter().flatten().copied().collect()
+}
+
+fn restore_ram(banks: &mut [[u8; RAM_BANK_SIZE]], data: &[u8]) {
+    for (bank, chunk) in banks.iter_mut().zip(data.chunks(RAM_BANK_SIZE)) {
+        bank[..chunk.len()].copy_from_slice(chunk);
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CartridgeState {
+    None,
+    Mbc1(Mbc1State),
+    Mbc2(Mbc2State),
+    Mbc3(Mbc3State),
+    Mbc5(Mbc5State),
+    HuC1(HuC1State),
+    HuC3(HuC3State),
+    Mbc7(Mbc7State),
+    PocketCamera(PocketCameraState),
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mbc1State {
+    pub ram_enabled: bool,
+    pub lower_bank: u8,
+    pub upper_bank: u8,
+    pub advanced_mode: bool,
+    pub ram: Vec<u8>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mbc2State {
+    pub ram_enabled: bool,
+    pub current_rom_bank: u8,
+    pub ram: Vec<u8>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mbc3State {
+    pub current_bank_or_rtc: u8,
+    pub ram_rtc_enabled: bool,
+    pub current_rom_bank: u8,
+    pub rtc: RtcState,
+    pub prelatch: bool,
+    pub ram: Vec<u8>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtcState {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub days: u16,
+    pub halt: bool,
+    pub carry: bool,
+    pub cycles: u32,
+    pub latched: [u8; 5],
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mbc5State {
+    pub ram_enabled: bool,
+    pub current_ram_bank: u8,
+    pub current_rom_bank: u16,
+    pub rumble: bool,
+    pub ram: Vec<u8>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HuC1State {
+    pub ir_mode: bool,
+    pub current_ram_bank: u8,
+    pub current_rom_bank: u8,
+    pub ram: Vec<u8>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HuC3State {
+    pub mode: u8,
+    pub current_ram_bank: u8,
+    pub current_rom_bank: u8,
+    pub minutes: u16,
+    pub days: u16,
+    pub seconds: u64,
+    pub epoch: u64,
+    pub access_index: u8,
+    pub access_flags: u8,
+    pub read_value: u8,
+    pub alarm_minutes: u16,
+    pub alarm_days: u16,
+    pub alarm_enabled: bool,
+    pub ram: Vec<u8>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mbc7State {
+    pub ram_enabled: bool,
+    pub ram_enabled_2: bool,
+    pub current_rom_bank: u8,
+    pub latch_ready: bool,
+    pub accelerometer_x: u16,
+    pub accelerometer_y: u16,
+    pub eeprom: Vec<u8>,
+    pub eeprom_state: EepromState,
+    pub eeprom_write_enabled: bool,
+    pub eeprom_chip_select: bool,
+    pub eeprom_clock: bool,
+    pub eeprom_data_in: bool,
+    pub eeprom_data_out: bool,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PocketCameraState {
+    pub ram_enabled: bool,
+    pub current_ram_bank: u8,
+    pub registers_mapped: bool,
+    pub current_rom_bank: u8,
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
+    pub registers: [u8; CAMERA_REGISTER_COUNT],
+    pub capture_cycles: u32,
+    /// Picture taken by the sensor, still needed while a capture is running.
+    pub frame: Vec<u8>,
+    pub ram: Vec<u8>,
 }
 
 pub struct ReadOnlyMemoryCartridge<RM: RomManager> {
@@ -72,15 +259,20 @@ enum MemoryMode {
     _4MBitRom32KByteRam,
 }
 
+/// Multicart boards are 1 MiB and hold one game per 256 KiB block, each with its own header.
+const MBC1M_ROM_BANKS: u16 = 64;
+const MBC1M_GAME_SIZE: usize = 0x40000;
+
 pub struct Mbc1Cartridge<RM: RomManager> {
     rom_manager: RM,
-    current_ram_bank: u8,
-    number_of_bank_rams: u8,
     ram_enabled: bool,
     ram_banks: Box<[[u8; 0xBFFF - 0xA000 + 1]]>,
-    current_rom_bank: u8,
-    rom_banks: u8,
+    lower_bank: u8,
+    upper_bank: u8,
+    rom_banks: u16,
     mode: MemoryMode,
+    multicart: bool,
+    battery: bool,
 }
 
 impl<RM: RomManager> Mbc1Cartridge<RM> {
@@ -94,68 +286,101 @@ impl<RM: RomManager> Mbc1Cartridge<RM> {
         return 0;
     }
 
-    pub fn new(rom_manager: RM, ram_banks: u8, rom_banks: u8) -> Self {
+    pub fn new(rom_manager: RM, ram_banks: u8, rom_banks: u16, battery: bool) -> Self {
+        let multicart = Self::detect_multicart(&rom_manager, rom_banks);
         Self {
             rom_manager,
             ram_banks: (0..ram_banks).map(|_| [0; 0xBFFF - 0xA000 + 1]).collect(),
             ram_enabled: false,
-            current_ram_bank: 0,
-            number_of_bank_rams: ram_banks,
-            current_rom_bank: 1,
+            lower_bank: 1,
+            upper_bank: 0,
             mode: MemoryMode::_16MBitRom8KByteRam,
-            rom_banks: rom_banks,
+            rom_banks,
+            multicart,
+            battery,
+        }
+    }
+
+    /// MBC1M boards wire only 4 bits of the lower bank register, they are told apart
+    /// from regular 1 MiB carts by the extra Nintendo logos at each 256 KiB boundary.
+    fn detect_multicart(rom_manager: &RM, rom_banks: u16) -> bool {
+        if rom_banks != MBC1M_ROM_BANKS {
+            return false;
+        }
+        let logo_count = (0..4)
+            .map(|game| game * MBC1M_GAME_SIZE + NINTENDO_LOGO_OFFSET)
+            .filter(|offset| rom_manager[*offset..*offset + NINTENDO_LOGO.len()] == NINTENDO_LOGO)
+            .count();
+        logo_count > 1
+    }
+
+    fn upper_bank_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    fn zero_bank(&self) -> u8 {
+        if self.mode == MemoryMode::_4MBitRom32KByteRam {
+            self.upper_bank << self.upper_bank_shift()
+        } else {
+            0
+        }
+    }
+
+    fn high_bank(&self) -> u8 {
+        let lower_mask = if self.multicart { 0x0F } else { 0x1F };
+        (self.upper_bank << self.upper_bank_shift()) | (self.lower_bank & lower_mask)
+    }
+
+    /// Bank mapped at `address`, wrapped to the banks the ROM actually has.
+    fn mapped_bank(&self, address: u16) -> u16 {
+        let bank = if address < 0x4000 {
+            self.zero_bank()
+        } else {
+            self.high_bank()
+        };
+        bank as u16 % self.rom_banks.max(1)
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.mode == MemoryMode::_4MBitRom32KByteRam {
+            self.upper_bank as usize % self.ram_banks.len()
+        } else {
+            0
         }
     }
 }
 
 impl<RM: RomManager> Cartridge for Mbc1Cartridge<RM> {
+    fn rom_bank(&self, address: u16) -> u16 {
+        self.mapped_bank(address)
+    }
+
     fn read_rom(&self, address: u16) -> u8 {
-        if Self::compare(address, 0x4000, 0x7FFF) == 0 {
-            let bank = if address < 0x4000 {
-                if self.mode == MemoryMode::_16MBitRom8KByteRam {
-                    0
-                } else {
-                    self.current_rom_bank & 0xE0
-                }
-            } else {
-                self.current_rom_bank
-            };
+        let bank = self.mapped_bank(address);
+        let index = address & 0x3FFF;
 
-            let bank_offset = ((bank as usize - 1) * (0x7FFF - 0x4000 + 1)) as usize + 0x4000;
-            let result = self.rom_manager.read_from_offset(
-                bank_offset,
-                (address - 0x4000) as usize,
-                self.current_rom_bank,
-            );
-            return result;
-        }
-        let result = self
-            .rom_manager
-            .read_from_offset(0x0000, address as usize, 0);
-        result
+        let bank_offset = bank as usize * (0x7FFF - 0x4000 + 1);
+        self.rom_manager
+            .read_from_offset(bank_offset, index as usize, bank)
     }
 
-    fn write_rom(&mut self, address: u16, mut data: u8) {
+    fn write_rom(&mut self, address: u16, data: u8) {
         if address < 0x2000 {
             self.ram_enabled = data & 0xF == 0xA;
         } else if address < 0x4000 {
-            let lower_bits = match (data as usize) & 0x1F {
+            // The zero check happens on all 5 bits, even on multicarts where only 4 are wired
+            self.lower_bank = match data & 0x1F {
                 0 => 1,
                 n => n,
             };
-            self.current_rom_bank = (((self.current_rom_bank as usize & 0x60) | lower_bits)
-                % self.rom_banks as usize) as u8;
         } else if address < 0x6000 {
-            if self.current_rom_bank > 0x20 {
-                let upper_bits = (data as u8 & 0x03) % (self.rom_banks >> 5);
-                self.current_rom_bank = self.current_rom_bank & 0x1F | (upper_bits << 5)
-            }
-            if self.number_of_bank_rams > 1 {
-                self.current_ram_bank = (data as u8) & 0x03;
-            }
+            self.upper_bank = data & 0x03;
         } else if address < 0x8000 {
-            data = data & 0b0000_0001;
-            if data == 1 {
+            if data & 0b0000_0001 == 1 {
                 self.mode = MemoryMode::_4MBitRom32KByteRam;
             } else {
                 self.mode = MemoryMode::_16MBitRom8KByteRam
@@ -164,29 +389,340 @@ impl<RM: RomManager> Cartridge for Mbc1Cartridge<RM> {
     }
 
     fn read_ram(&self, address: u16) -> u8 {
-        if !self.ram_enabled {
+        if !self.ram_enabled || self.ram_banks.is_empty() {
             return 0xFF;
         }
+        self.ram_banks[self.ram_bank()][address as usize - 0xA000]
+    }
 
-        let rambank = if self.mode == MemoryMode::_4MBitRom32KByteRam {
-            self.current_ram_bank
-        } else {
-            0
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled || self.ram_banks.is_empty() {
+            return;
+        }
+        let rambank = self.ram_bank();
+        self.ram_banks[rambank][address as usize - 0xA000] = value;
+    }
+
+    fn save_ram(&self) -> Vec<u8> {
+        if !self.battery {
+            return Vec::new();
+        }
+        dump_ram(&self.ram_banks)
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram_banks, data);
+    }
+
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::Mbc1(Mbc1State {
+            ram_enabled: self.ram_enabled,
+            lower_bank: self.lower_bank,
+            upper_bank: self.upper_bank,
+            advanced_mode: self.mode == MemoryMode::_4MBitRom32KByteRam,
+            ram: dump_ram(&self.ram_banks),
+        })
+    }
+
+    fn load_state(&mut self, state: CartridgeState) {
+        if let CartridgeState::Mbc1(state) = state {
+            self.ram_enabled = state.ram_enabled;
+            self.lower_bank = state.lower_bank;
+            self.upper_bank = state.upper_bank;
+            self.mode = if state.advanced_mode {
+                MemoryMode::_4MBitRom32KByteRam
+            } else {
+                MemoryMode::_16MBitRom8KByteRam
+            };
+            restore_ram(&mut self.ram_banks, &state.ram);
+        }
+    }
+}
+
+const MBC2_RAM_SIZE: usize = 512;
+
+pub struct Mbc2Cartridge<RM: RomManager> {
+    rom_manager: Rom<RM>,
+    ram_enabled: bool,
+    ram: [u8; MBC2_RAM_SIZE],
+    current_rom_bank: u8,
+    rom_banks: u16,
+    battery: bool,
+}
+
+impl<RM: RomManager> Mbc2Cartridge<RM> {
+    pub fn new(rom_manager: Rom<RM>) -> Self {
+        let rom_banks = rom_manager.rom_size.banks();
+        let battery = rom_manager.rom_type.battery();
+        let mut cartridge = Self {
+            rom_manager,
+            ram_enabled: false,
+            ram: [0; MBC2_RAM_SIZE],
+            current_rom_bank: 1,
+            rom_banks,
+            battery,
         };
+        if cartridge.battery {
+            cartridge.rom_manager.data.load_to_bank(
+                &cartridge.rom_manager.title,
+                0,
+                &mut cartridge.ram,
+            );
+        }
+        cartridge
+    }
+}
+
+impl<RM: RomManager> Cartridge for Mbc2Cartridge<RM> {
+    fn rom_bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
+        } else {
+            self.current_rom_bank as u16
+        }
+    }
 
-        self.ram_banks[rambank as usize][address as usize - 0xA000 as usize]
+    fn read_rom(&self, address: u16) -> u8 {
+        if address >= 0x4000 {
+            let bank_offset = self.current_rom_bank as usize * (0x7FFF - 0x4000 + 1);
+            return self.rom_manager.data.read_from_offset(
+                bank_offset,
+                (address - 0x4000) as usize,
+                self.current_rom_bank as u16,
+            );
+        }
+        self.rom_manager
+            .data
+            .read_from_offset(0x0000, address as usize, 0)
     }
 
-    fn write_ram(&mut self, address: u16, value: u8) {
-        if !self.ram_enabled || self.ram_banks.len() == 0 {
+    fn write_rom(&mut self, address: u16, data: u8) {
+        if address >= 0x4000 {
             return;
         }
-        let rambank = if self.mode == MemoryMode::_4MBitRom32KByteRam {
-            self.current_ram_bank
+        // Address bit 8 selects between the RAM enable and the ROM bank register
+        if address & 0x0100 == 0 {
+            let current_state = self.ram_enabled;
+            self.ram_enabled = data & 0x0F == 0x0A;
+            if current_state && !self.ram_enabled && self.battery {
+                self.rom_manager
+                    .data
+                    .save(&self.rom_manager.title, 0, &self.ram);
+            }
         } else {
-            0
-        };
-        self.ram_banks[rambank as usize][address as usize - 0xA000 as usize] = value;
+            let bank = (data & 0x0F).max(1);
+            self.current_rom_bank = (bank as u16 % self.rom_banks.max(2)) as u8;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        // Only the lower nibble is wired, the upper one reads as open bus
+        0xF0 | self.ram[address as usize & (MBC2_RAM_SIZE - 1)]
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        self.ram[address as usize & (MBC2_RAM_SIZE - 1)] = value & 0x0F;
+    }
+
+    fn save_ram(&self) -> Vec<u8> {
+        if !self.battery {
+            return Vec::new();
+        }
+        self.ram.to_vec()
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        for (nibble, byte) in self.ram.iter_mut().zip(data.iter()) {
+            *nibble = byte & 0x0F;
+        }
+    }
+
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::Mbc2(Mbc2State {
+            ram_enabled: self.ram_enabled,
+            current_rom_bank: self.current_rom_bank,
+            ram: self.ram.to_vec(),
+        })
+    }
+
+    fn load_state(&mut self, state: CartridgeState) {
+        if let CartridgeState::Mbc2(state) = state {
+            self.ram_enabled = state.ram_enabled;
+            self.current_rom_bank = state.current_rom_bank;
+            let length = state.ram.len().min(MBC2_RAM_SIZE);
+            self.ram[..length].copy_from_slice(&state.ram[..length]);
+        }
+    }
+}
+
+const RTC_CYCLES_PER_SECOND: u32 = 4_194_304;
+
+/// MBC3 real time clock. It counts emulated cycles so replays stay deterministic, or follows
+/// the host clock when synced.
+struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halt: bool,
+    carry: bool,
+    cycles: u32,
+    latched: [u8; 5],
+    host_sync: bool,
+    host_time: u64,
+}
+
+impl Rtc {
+    fn new() -> Self {
+        Self {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halt: false,
+            carry: false,
+            cycles: 0,
+            latched: [0; 5],
+            host_sync: false,
+            host_time: 0,
+        }
+    }
+
+    fn step(&mut self, cycles: u8) {
+        if self.halt {
+            return;
+        }
+        self.cycles += cycles as u32;
+        if self.cycles >= RTC_CYCLES_PER_SECOND {
+            self.cycles -= RTC_CYCLES_PER_SECOND;
+            self.tick();
+        }
+    }
+
+    /// Advances by the host seconds elapsed since the previous sync, halted time is dropped.
+    fn sync(&mut self, now: u64) {
+        let elapsed = now.saturating_sub(self.host_time);
+        self.host_time = now;
+        if !self.halt {
+            self.advance(elapsed);
+        }
+    }
+
+    /// Counters only carry when they pass their last valid value, a value written out of
+    /// range keeps counting up to the register width and wraps without carrying.
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days = (self.days + 1) & 0x1FF;
+        if self.days == 0 {
+            self.carry = true;
+        }
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+        let total = ((self.days as u64 * 24 + self.hours as u64) * 60 + self.minutes as u64) * 60
+            + self.seconds as u64
+            + seconds;
+        let days = total / (3600 * 24);
+        if days > 0x1FF {
+            self.carry = true;
+        }
+        self.seconds = (total % 60) as u8;
+        self.minutes = ((total / 60) % 60) as u8;
+        self.hours = ((total / 3600) % 24) as u8;
+        self.days = (days & 0x1FF) as u16;
+    }
+
+    fn registers(&self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            ((self.days >> 8) as u8 & 0x01)
+                | if self.halt { 0x40 } else { 0 }
+                | if self.carry { 0x80 } else { 0 },
+        ]
+    }
+
+    fn latch(&mut self) {
+        self.latched = self.registers();
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        self.latched[(register - 0x08) as usize]
+    }
+
+    /// Writes go to the live counters and show up in the latched copy right away.
+    fn write(&mut self, register: u8, value: u8) {
+        match register {
+            0x08 => {
+                self.seconds = value & 0x3F;
+                // Writing the seconds resets the prescaler
+                self.cycles = 0;
+            }
+            0x09 => self.minutes = value & 0x3F,
+            0x0a => self.hours = value & 0x1F,
+            0x0b => self.days = (self.days & 0x100) | value as u16,
+            0x0c => {
+                self.days = (self.days & 0xFF) | ((value as u16 & 0x01) << 8);
+                self.halt = value & 0x40 != 0;
+                self.carry = value & 0x80 != 0;
+            }
+            _ => return,
+        }
+        let index = (register - 0x08) as usize;
+        self.latched[index] = self.registers()[index];
+    }
+
+    fn create_state(&self) -> RtcState {
+        RtcState {
+            seconds: self.seconds,
+            minutes: self.minutes,
+            hours: self.hours,
+            days: self.days,
+            halt: self.halt,
+            carry: self.carry,
+            cycles: self.cycles,
+            latched: self.latched,
+        }
+    }
+
+    fn load_state(&mut self, state: RtcState) {
+        self.seconds = state.seconds;
+        self.minutes = state.minutes;
+        self.hours = state.hours;
+        self.days = state.days;
+        self.halt = state.halt;
+        self.carry = state.carry;
+        self.cycles = state.cycles.min(RTC_CYCLES_PER_SECOND - 1);
+        self.latched = state.latched;
     }
 }
 
@@ -196,14 +732,12 @@ pub struct Mbc3Cartridge<RM: RomManager> {
     ram_rtc_enabled: bool,
     ram_banks: Box<[[u8; 0xBFFF - 0xA000 + 1]]>,
     current_rom_bank: u8,
+    rom_banks: u16,
+    // MBC30 decodes eight RAM banks and a full byte of ROM bank
+    mbc30: bool,
+    has_timer: bool,
 
-    //
-    rtc_secs: u8,
-    rtc_mins: u8,
-    rtc_hours: u8,
-    rtc_day_low: u8,
-    rtc_day_high: u8,
-    epoch: u64,
+    rtc: Rtc,
     prelatch: bool,
 }
 
@@ -219,20 +753,20 @@ impl<RM: RomManager> Mbc3Cartridge<RM> {
     }
 
     pub fn new(rom_manager: Rom<RM>) -> Self {
-        let epoch = rom_manager.data.clock() / 1_000_000;
         let banks = rom_manager.ram_size.banks();
+        let rom_banks = rom_manager.rom_size.banks();
+        let mbc30 = banks > 4 || rom_banks > 128;
+        let has_timer = rom_manager.rom_type.timer();
         let mut cartridge = Self {
             rom_manager,
             ram_banks: (0..banks).map(|_| [0; 0xBFFF - 0xA000 + 1]).collect(),
             current_bank_or_rtc: 0,
             ram_rtc_enabled: false,
             current_rom_bank: 1,
-            rtc_secs: 0,
-            rtc_mins: 0,
-            rtc_hours: 0,
-            rtc_day_low: 0,
-            rtc_day_high: 0,
-            epoch: epoch,
+            rom_banks,
+            mbc30,
+            has_timer,
+            rtc: Rtc::new(),
             prelatch: false,
         };
         for (index, bank) in &mut cartridge.ram_banks.iter_mut().enumerate() {
@@ -245,62 +779,79 @@ impl<RM: RomManager> Mbc3Cartridge<RM> {
         cartridge
     }
 
-    fn update_epoch(&mut self) {
-        self.epoch = self.epoch();
+    /// Seconds since the UNIX epoch.
+    fn host_seconds(&self) -> u64 {
+        self.rom_manager.data.clock() / 1_000_000
     }
 
-    fn epoch(&self) -> u64 {
-        self.rom_manager.data.clock() / 1_000_000
+    /// RAM bank selected for 0xA000-0xBFFF, `None` when the selector is not wired to RAM.
+    fn ram_bank(&self) -> Option<usize> {
+        let selectors = if self.mbc30 { 0x08 } else { 0x04 };
+        if self.current_bank_or_rtc >= selectors || self.ram_banks.is_empty() {
+            return None;
+        }
+        Some(self.current_bank_or_rtc as usize % self.ram_banks.len())
     }
 
-    fn day(&self) -> u64 {
-        ((self.rtc_day_high as u64 & 1) << 8) & self.rtc_day_low as u64
+    fn rtc_selected(&self) -> bool {
+        self.has_timer && (0x08..=0x0c).contains(&self.current_bank_or_rtc)
     }
 
-    fn dhms_to_secs(&self) -> u64 {
-        let d = self.day();
-        let s = self.rtc_secs as u64;
-        let m = self.rtc_mins as u64;
-        let h = self.rtc_hours as u64;
-        (d * 24 + h) * 3600 + m * 60 + s
+    /// Appends the 48 byte footer shared by VBA-M, BGB and mGBA: the live and latched
+    /// registers as five little endian u32 each, followed by a u64 timestamp in seconds.
+    fn write_rtc_footer(&self, data: &mut Vec<u8>) {
+        let live = self.rtc.registers();
+        for register in live.iter().chain(self.rtc.latched.iter()) {
+            data.extend_from_slice(&(*register as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&self.host_seconds().to_le_bytes());
     }
 
-    fn secs_to_dhms(&mut self, secs: u64) {
-        let s = secs % 60;
-        let m = (secs / 60) % 60;
-        let h = (secs / 3600) % 24;
-        let d = secs / (3600 * 24);
-        self.rtc_secs = s as u8;
-        self.rtc_mins = m as u8;
-        self.rtc_hours = h as u8;
-        self.rtc_day_low = d as u8;
-        self.rtc_day_high = (self.rtc_day_high & !1) | ((d >> 8) & 1) as u8;
+    fn read_rtc_footer(&mut self, footer: &[u8]) {
+        let register = |index: usize| footer[index * 4];
+        for index in 0..5 {
+            self.rtc.write(0x08 + index as u8, register(index));
+            self.rtc.latched[index] = register(index + 5);
+        }
+        self.rtc.cycles = 0;
+        if self.rtc.host_sync {
+            let mut timestamp = [0; 8];
+            timestamp.copy_from_slice(&footer[40..48]);
+            let now = self.host_seconds();
+            self.rtc.host_time = u64::from_le_bytes(timestamp);
+            self.rtc.sync(now);
+        }
     }
+}
 
-    fn latch(&mut self) {
-        let new_epoch = if self.rtc_day_high & 0x40 == 0 {
-            self.epoch()
+impl<RM: RomManager> Cartridge for Mbc3Cartridge<RM> {
+    fn rom_bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
         } else {
-            // Halt
-            self.epoch
-        };
-        let elapsed = new_epoch - self.epoch;
-
-        let last_day = self.day();
-        let last_secs = self.dhms_to_secs();
-        self.secs_to_dhms(last_secs + elapsed);
-        let new_day = self.day();
+            self.current_rom_bank as u16
+        }
+    }
 
-        // Overflow
-        if new_day < last_day {
-            self.rtc_day_high |= 0x80;
+    fn step(&mut self, cycles: u8) {
+        if !self.rtc.host_sync {
+            self.rtc.step(cycles);
+            return;
+        }
+        // The host clock is only polled once per emulated second
+        self.rtc.cycles += cycles as u32;
+        if self.rtc.cycles >= RTC_CYCLES_PER_SECOND {
+            self.rtc.cycles -= RTC_CYCLES_PER_SECOND;
+            let now = self.host_seconds();
+            self.rtc.sync(now);
         }
+    }
 
-        self.epoch = new_epoch;
+    fn set_rtc_host_sync(&mut self, enabled: bool) {
+        self.rtc.host_sync = enabled;
+        self.rtc.host_time = self.host_seconds();
     }
-}
 
-impl<RM: RomManager> Cartridge for Mbc3Cartridge<RM> {
     fn read_rom(&self, address: u16) -> u8 {
         self.get_byte(address)
     }
@@ -311,7 +862,7 @@ impl<RM: RomManager> Cartridge for Mbc3Cartridge<RM> {
 
     fn read_ram(&self, address: u16) -> u8 {
         if !self.ram_rtc_enabled {
-            return 0;
+            return 0xFF;
         }
         self.get_byte(address)
     }
@@ -319,66 +870,88 @@ impl<RM: RomManager> Cartridge for Mbc3Cartridge<RM> {
     fn write_ram(&mut self, address: u16, value: u8) {
         self.set_byte(address, value)
     }
+
+    fn save_ram(&self) -> Vec<u8> {
+        if !self.rom_manager.rom_type.battery() {
+            return Vec::new();
+        }
+        let mut data = dump_ram(&self.ram_banks);
+        if self.has_timer {
+            self.write_rtc_footer(&mut data);
+        }
+        data
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram_banks, data);
+        let ram_size = self.ram_banks.len() * RAM_BANK_SIZE;
+        if self.has_timer && data.len() >= ram_size + RTC_FOOTER_SIZE {
+            self.read_rtc_footer(&data[ram_size..]);
+        }
+    }
+
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::Mbc3(Mbc3State {
+            current_bank_or_rtc: self.current_bank_or_rtc,
+            ram_rtc_enabled: self.ram_rtc_enabled,
+            current_rom_bank: self.current_rom_bank,
+            rtc: self.rtc.create_state(),
+            prelatch: self.prelatch,
+            ram: dump_ram(&self.ram_banks),
+        })
+    }
+
+    fn load_state(&mut self, state: CartridgeState) {
+        if let CartridgeState::Mbc3(state) = state {
+            self.current_bank_or_rtc = state.current_bank_or_rtc;
+            self.ram_rtc_enabled = state.ram_rtc_enabled;
+            self.current_rom_bank = (state.current_rom_bank as u16 % self.rom_banks.max(1)) as u8;
+            self.rtc.load_state(state.rtc);
+            if self.rtc.host_sync {
+                self.rtc.host_time = self.host_seconds();
+            }
+            self.prelatch = state.prelatch;
+            restore_ram(&mut self.ram_banks, &state.ram);
+        }
+    }
 }
 impl<RM: RomManager> Memory for Mbc3Cartridge<RM> {
     fn set_byte(&mut self, address: u16, data: u8) {
         if address < 0x2000 {
             let current_state = self.ram_rtc_enabled;
             self.ram_rtc_enabled = (data & 0b0000_1010) != 0;
-            if !self.ram_rtc_enabled
-                && current_state
-                && (self.current_bank_or_rtc == 0x00
-                    || self.current_bank_or_rtc == 0x01
-                    || self.current_bank_or_rtc == 0x02
-                    || self.current_bank_or_rtc == 0x03)
-            {
-                //SAVE ROM
-                self.rom_manager.data.save(
-                    &self.rom_manager.title,
-                    self.current_bank_or_rtc,
-                    &self.ram_banks[self.current_bank_or_rtc as usize],
-                )
+            if !self.ram_rtc_enabled && current_state {
+                if let Some(bank) = self.ram_bank() {
+                    //SAVE ROM
+                    self.rom_manager.data.save(
+                        &self.rom_manager.title,
+                        bank as u8,
+                        &self.ram_banks[bank],
+                    )
+                }
             }
         } else if address < 0x4000 {
-            self.current_rom_bank = (data & 0x7f).max(1);
+            let mask = if self.mbc30 { 0xff } else { 0x7f };
+            let bank = ((data & mask) as u16 % self.rom_banks.max(1)) as u8;
+            self.current_rom_bank = bank.max(1);
         } else if address < 0x6000 {
             self.current_bank_or_rtc = data;
         } else if address < 0x8000 {
             if self.prelatch {
                 if data == 0x01 {
-                    self.latch()
+                    self.rtc.latch()
                 }
                 self.prelatch = false;
             } else if data == 0x00 {
                 self.prelatch = true;
             }
         } else if Self::compare(address, 0xa000, 0xbfff) == 0 {
-            match self.current_bank_or_rtc {
-                x if x == 0x00 || x == 0x01 || x == 0x02 || x == 0x03 => {
-                    self.ram_banks[x as usize][address as usize - 0xA000] = data
-                }
-                0x08 => {
-                    self.rtc_secs = data;
-                    self.update_epoch();
-                }
-                0x09 => {
-                    self.rtc_mins = data;
-                    self.update_epoch();
-                }
-                0x0a => {
-                    self.rtc_hours = data;
-                    self.update_epoch();
-                }
-                0x0b => {
-                    self.rtc_day_low = data;
-                    self.update_epoch();
-                }
-                0x0c => {
-                    self.rtc_day_high = data;
-                    self.update_epoch();
-                }
-                s => unimplemented!("Unknown selector: {:02x}", s),
-            };
+            // Writes to selectors that map nothing are dropped
+            if let Some(bank) = self.ram_bank() {
+                self.ram_banks[bank][address as usize - 0xA000] = data
+            } else if self.rtc_selected() {
+                self.rtc.write(self.current_bank_or_rtc, data)
+            }
         }
     }
 
@@ -389,20 +962,17 @@ impl<RM: RomManager> Memory for Mbc3Cartridge<RM> {
             let result = self.rom_manager.data.read_from_offset(
                 bank_offset,
                 (address - 0x4000) as usize,
-                self.current_rom_bank,
+                self.current_rom_bank as u16,
             );
             return result;
         } else if Self::compare(address, 0xa000, 0xbfff) == 0 {
-            match self.current_bank_or_rtc {
-                x if x == 0x00 || x == 0x01 || x == 0x02 || x == 0x03 => {
-                    self.ram_banks[x as usize][address as usize - 0xA000]
-                }
-                0x08 => self.rtc_secs,
-                0x09 => self.rtc_mins,
-                0x0a => self.rtc_hours,
-                0x0b => self.rtc_day_low,
-                0x0c => self.rtc_day_high,
-                s => unimplemented!("Unknown selector: {:02x}", s),
+            if let Some(bank) = self.ram_bank() {
+                self.ram_banks[bank][address as usize - 0xA000]
+            } else if self.rtc_selected() {
+                self.rtc.read(self.current_bank_or_rtc)
+            } else {
+                // Open bus
+                0xFF
             }
         } else {
             self.rom_manager
@@ -411,3 +981,2135 @@ impl<RM: RomManager> Memory for Mbc3Cartridge<RM> {
         }
     }
 }
+
+pub struct Mbc5Cartridge<RM: RomManager> {
+    rom_manager: Rom<RM>,
+    ram_enabled: bool,
+    ram_banks: Box<[[u8; 0xBFFF - 0xA000 + 1]]>,
+    current_ram_bank: u8,
+    current_rom_bank: u16,
+    rom_banks: u16,
+    battery: bool,
+    has_rumble: bool,
+    rumble: bool,
+}
+
+impl<RM: RomManager> Mbc5Cartridge<RM> {
+    #[inline(always)]
+    pub fn compare(value: u16, from: u16, to: u16) -> isize {
+        if value < from {
+            value as isize - from as isize
+        } else if value > to {
+            value as isize - to as isize
+        } else {
+            0
+        }
+    }
+
+    pub fn new(rom_manager: Rom<RM>) -> Self {
+        let banks = rom_manager.ram_size.banks();
+        let rom_banks = rom_manager.rom_size.banks();
+        let battery = rom_manager.rom_type.battery();
+        let has_rumble = rom_manager.rom_type.rumble();
+        let mut cartridge = Self {
+            rom_manager,
+            ram_enabled: false,
+            ram_banks: (0..banks).map(|_| [0; 0xBFFF - 0xA000 + 1]).collect(),
+            current_ram_bank: 0,
+            current_rom_bank: 1,
+            rom_banks,
+            battery,
+            has_rumble,
+            rumble: false,
+        };
+        if cartridge.battery {
+            for (index, bank) in &mut cartridge.ram_banks.iter_mut().enumerate() {
+                cartridge.rom_manager.data.load_to_bank(
+                    &cartridge.rom_manager.title,
+                    index as u8,
+                    bank,
+                );
+            }
+        }
+        cartridge
+    }
+
+    fn flush_banks(&mut self) {
+        for (index, bank) in self.ram_banks.iter().enumerate() {
+            self.rom_manager
+                .data
+                .save(&self.rom_manager.title, index as u8, bank);
+        }
+    }
+}
+
+impl<RM: RomManager> Cartridge for Mbc5Cartridge<RM> {
+    fn rom_bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
+        } else {
+            self.current_rom_bank
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        if Self::compare(address, 0x4000, 0x7FFF) == 0 {
+            let bank_offset = self.current_rom_bank as usize * (0x7FFF - 0x4000 + 1);
+            return self.rom_manager.data.read_from_offset(
+                bank_offset,
+                (address - 0x4000) as usize,
+                self.current_rom_bank,
+            );
+        }
+        self.rom_manager
+            .data
+            .read_from_offset(0x0000, address as usize, 0)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        if address < 0x2000 {
+            let current_state = self.ram_enabled;
+            self.ram_enabled = data & 0x0F == 0x0A;
+            if current_state && !self.ram_enabled && self.battery {
+                self.flush_banks();
+            }
+        } else if address < 0x3000 {
+            self.current_rom_bank = (self.current_rom_bank & 0x100) | data as u16;
+            self.current_rom_bank %= self.rom_banks.max(1);
+        } else if address < 0x4000 {
+            self.current_rom_bank = (self.current_rom_bank & 0xFF) | (((data & 0x01) as u16) << 8);
+            self.current_rom_bank %= self.rom_banks.max(1);
+        } else if address < 0x6000 {
+            if self.has_rumble {
+                // Bit 3 drives the motor on rumble boards, leaving 3 bits for the RAM bank
+                self.rumble = data & 0x08 != 0;
+                self.current_ram_bank = data & 0x07;
+            } else {
+                self.current_ram_bank = data & 0x0F;
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || self.ram_banks.is_empty() {
+            return 0xFF;
+        }
+        let bank = self.current_ram_bank as usize % self.ram_banks.len();
+        self.ram_banks[bank][address as usize - 0xA000]
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled || self.ram_banks.is_empty() {
+            return;
+        }
+        let bank = self.current_ram_bank as usize % self.ram_banks.len();
+        self.ram_banks[bank][address as usize - 0xA000] = value;
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn save_ram(&self) -> Vec<u8> {
+        if !self.battery {
+            return Vec::new();
+        }
+        dump_ram(&self.ram_banks)
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram_banks, data);
+    }
+
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::Mbc5(Mbc5State {
+            ram_enabled: self.ram_enabled,
+            current_ram_bank: self.current_ram_bank,
+            current_rom_bank: self.current_rom_bank,
+            rumble: self.rumble,
+            ram: dump_ram(&self.ram_banks),
+        })
+    }
+
+    fn load_state(&mut self, state: CartridgeState) {
+        if let CartridgeState::Mbc5(state) = state {
+            self.ram_enabled = state.ram_enabled;
+            self.current_ram_bank = state.current_ram_bank;
+            self.current_rom_bank = state.current_rom_bank % self.rom_banks.max(1);
+            self.rumble = state.rumble;
+            restore_ram(&mut self.ram_banks, &state.ram);
+        }
+    }
+}
+
+pub struct HuC1Cartridge<RM: RomManager> {
+    rom_manager: Rom<RM>,
+    ir_mode: bool,
+    // Games still write 0x0A before saving and something else after, the banks are
+    // flushed when they are done
+    ram_enabled: bool,
+    ram_banks: Box<[[u8; 0xBFFF - 0xA000 + 1]]>,
+    current_ram_bank: u8,
+    current_rom_bank: u8,
+    rom_banks: u16,
+    infrared: Box<dyn InfraredPort>,
+}
+
+impl<RM: RomManager> HuC1Cartridge<RM> {
+    pub fn new(rom_manager: Rom<RM>) -> Self {
+        let banks = rom_manager.ram_size.banks();
+        let rom_banks = rom_manager.rom_size.banks();
+        let mut cartridge = Self {
+            rom_manager,
+            ir_mode: false,
+            ram_enabled: false,
+            ram_banks: (0..banks).map(|_| [0; 0xBFFF - 0xA000 + 1]).collect(),
+            current_ram_bank: 0,
+            current_rom_bank: 1,
+            rom_banks,
+            infrared: Box::new(NullInfrared),
+        };
+        for (index, bank) in &mut cartridge.ram_banks.iter_mut().enumerate() {
+            cartridge.rom_manager.data.load_to_bank(
+                &cartridge.rom_manager.title,
+                index as u8,
+                bank,
+            );
+        }
+        cartridge
+    }
+
+    fn flush_banks(&mut self) {
+        for (index, bank) in self.ram_banks.iter().enumerate() {
+            self.rom_manager
+                .data
+                .save(&self.rom_manager.title, index as u8, bank);
+        }
+    }
+}
+
+impl<RM: RomManager> Cartridge for HuC1Cartridge<RM> {
+    fn rom_bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
+        } else {
+            self.current_rom_bank as u16
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        if address >= 0x4000 {
+            let bank_offset = self.current_rom_bank as usize * (0x7FFF - 0x4000 + 1);
+            return self.rom_manager.data.read_from_offset(
+                bank_offset,
+                (address - 0x4000) as usize,
+                self.current_rom_bank as u16,
+            );
+        }
+        self.rom_manager
+            .data
+            .read_from_offset(0x0000, address as usize, 0)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        if address < 0x2000 {
+            // There is no real RAM enable, 0x0E maps the IR port and anything else the RAM
+            self.ir_mode = data & 0x0F == 0x0E;
+            let current_state = self.ram_enabled;
+            self.ram_enabled = data & 0x0F == 0x0A;
+            if current_state && !self.ram_enabled {
+                self.flush_banks();
+            }
+        } else if address < 0x4000 {
+            self.current_rom_bank = ((data & 0x3F).max(1) as u16 % self.rom_banks.max(2)) as u8;
+        } else if address < 0x6000 {
+            self.current_ram_bank = data & 0x03;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ir_mode {
+            return 0xC0 | self.infrared.light_detected() as u8;
+        }
+        if self.ram_banks.is_empty() {
+            return 0xFF;
+        }
+        let bank = self.current_ram_bank as usize % self.ram_banks.len();
+        self.ram_banks[bank][address as usize - 0xA000]
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ir_mode {
+            self.infrared.set_led(value & 0x01 != 0);
+            return;
+        }
+        if self.ram_banks.is_empty() {
+            return;
+        }
+        let bank = self.current_ram_bank as usize % self.ram_banks.len();
+        self.ram_banks[bank][address as usize - 0xA000] = value;
+    }
+
+    fn attach_infrared(&mut self, port: Box<dyn InfraredPort>) {
+        self.infrared = port;
+    }
+
+    fn save_ram(&self) -> Vec<u8> {
+        dump_ram(&self.ram_banks)
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram_banks, data);
+    }
+
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::HuC1(HuC1State {
+            ir_mode: self.ir_mode,
+            current_ram_bank: self.current_ram_bank,
+            current_rom_bank: self.current_rom_bank,
+            ram: dump_ram(&self.ram_banks),
+        })
+    }
+
+    fn load_state(&mut self, state: CartridgeState) {
+        if let CartridgeState::HuC1(state) = state {
+            self.ir_mode = state.ir_mode;
+            self.current_ram_bank = state.current_ram_bank;
+            self.current_rom_bank = state.current_rom_bank;
+            restore_ram(&mut self.ram_banks, &state.ram);
+        }
+    }
+}
+
+const HUC3_MINUTES_PER_DAY: u16 = 24 * 60;
+// Days are exposed as three nibbles of RTC memory and wrap after 4096
+const HUC3_DAYS_MASK: u16 = 0xFFF;
+
+pub struct HuC3Cartridge<RM: RomManager> {
+    rom_manager: Rom<RM>,
+    mode: u8,
+    ram_banks: Box<[[u8; 0xBFFF - 0xA000 + 1]]>,
+    current_ram_bank: u8,
+    current_rom_bank: u8,
+    rom_banks: u16,
+    infrared: Box<dyn InfraredPort>,
+
+    //
+    minutes: u16,
+    days: u16,
+    seconds: u64,
+    epoch: u64,
+    access_index: u8,
+    access_flags: u8,
+    read_value: u8,
+    alarm_minutes: u16,
+    alarm_days: u16,
+    alarm_enabled: bool,
+}
+
+impl<RM: RomManager> HuC3Cartridge<RM> {
+    pub fn new(rom_manager: Rom<RM>) -> Self {
+        let epoch = rom_manager.data.clock() / 1_000_000;
+        let banks = rom_manager.ram_size.banks();
+        let rom_banks = rom_manager.rom_size.banks();
+        let mut cartridge = Self {
+            rom_manager,
+            mode: 0,
+            ram_banks: (0..banks).map(|_| [0; 0xBFFF - 0xA000 + 1]).collect(),
+            current_ram_bank: 0,
+            current_rom_bank: 1,
+            rom_banks,
+            infrared: Box::new(NullInfrared),
+            minutes: 0,
+            days: 0,
+            seconds: 0,
+            epoch,
+            access_index: 0,
+            access_flags: 0,
+            read_value: 0,
+            alarm_minutes: 0,
+            alarm_days: 0,
+            alarm_enabled: false,
+        };
+        for (index, bank) in &mut cartridge.ram_banks.iter_mut().enumerate() {
+            cartridge.rom_manager.data.load_to_bank(
+                &cartridge.rom_manager.title,
+                index as u8,
+                bank,
+            );
+        }
+        cartridge
+    }
+
+    fn flush_banks(&mut self) {
+        for (index, bank) in self.ram_banks.iter().enumerate() {
+            self.rom_manager
+                .data
+                .save(&self.rom_manager.title, index as u8, bank);
+        }
+    }
+
+    fn update_clock(&mut self) {
+        let now = self.rom_manager.data.clock() / 1_000_000;
+        let elapsed = now.saturating_sub(self.epoch);
+        self.epoch = now;
+
+        let total_secs = self.seconds + elapsed;
+        self.seconds = total_secs % 60;
+        let total_minutes = self.minutes as u64 + total_secs / 60;
+        let total_days = self.days as u64 + total_minutes / HUC3_MINUTES_PER_DAY as u64;
+        self.minutes = (total_minutes % HUC3_MINUTES_PER_DAY as u64) as u16;
+        self.days = (total_days & HUC3_DAYS_MASK as u64) as u16;
+    }
+
+    fn nibble(value: u16, index: u8) -> u8 {
+        ((value >> (index * 4)) & 0x0F) as u8
+    }
+
+    fn set_nibble(value: &mut u16, index: u8, data: u8) {
+        *value &= !(0x0F << (index * 4));
+        *value |= ((data & 0x0F) as u16) << (index * 4);
+    }
+
+    fn execute_command(&mut self, data: u8) {
+        let argument = data & 0x0F;
+        match data >> 4 {
+            // Read the RTC memory and advance
+            0x1 => {
+                self.update_clock();
+                self.read_value = match self.access_index {
+                    index @ 0..=2 => Self::nibble(self.minutes, index),
+                    index @ 3..=5 => Self::nibble(self.days, index - 3),
+                    _ => 0,
+                };
+                self.access_index = self.access_index.wrapping_add(1);
+            }
+            // Write the RTC memory, 0x3 also advances
+            command @ (0x2 | 0x3) => {
+                self.update_clock();
+                match self.access_index {
+                    index @ 0..=2 => Self::set_nibble(&mut self.minutes, index, argument),
+                    index @ 3..=5 => Self::set_nibble(&mut self.days, index - 3, argument),
+                    index @ 0x58..=0x5A => {
+                        Self::set_nibble(&mut self.alarm_minutes, index - 0x58, argument)
+                    }
+                    index @ 0x5B..=0x5D => {
+                        Self::set_nibble(&mut self.alarm_days, index - 0x5B, argument)
+                    }
+                    0x5F => self.alarm_enabled = argument & 0x01 != 0,
+                    _ => {}
+                }
+                if command == 0x3 {
+                    self.access_index = self.access_index.wrapping_add(1);
+                }
+            }
+            // Select the low/high nibble of the RTC memory address
+            0x4 => self.access_index = (self.access_index & 0xF0) | argument,
+            0x5 => self.access_index = (self.access_index & 0x0F) | (argument << 4),
+            0x6 => self.access_flags = argument,
+            _ => {}
+        }
+    }
+}
+
+impl<RM: RomManager> Cartridge for HuC3Cartridge<RM> {
+    fn rom_bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
+        } else {
+            self.current_rom_bank as u16
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        if address >= 0x4000 {
+            let bank_offset = self.current_rom_bank as usize * (0x7FFF - 0x4000 + 1);
+            return self.rom_manager.data.read_from_offset(
+                bank_offset,
+                (address - 0x4000) as usize,
+                self.current_rom_bank as u16,
+            );
+        }
+        self.rom_manager
+            .data
+            .read_from_offset(0x0000, address as usize, 0)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        if address < 0x2000 {
+            let previous_mode = self.mode;
+            self.mode = data & 0x0F;
+            if previous_mode == 0x0A && self.mode != 0x0A {
+                self.flush_banks();
+            }
+        } else if address < 0x4000 {
+            self.current_rom_bank = ((data & 0x7F) as u16 % self.rom_banks.max(2)) as u8;
+        } else if address < 0x6000 {
+            self.current_ram_bank = data & 0x03;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.mode {
+            0x00 | 0x0A => {
+                if self.ram_banks.is_empty() {
+                    return 0xFF;
+                }
+                let bank = self.current_ram_bank as usize % self.ram_banks.len();
+                self.ram_banks[bank][address as usize - 0xA000]
+            }
+            0x0C => {
+                if self.access_flags == 0x02 {
+                    0x01
+                } else {
+                    self.read_value
+                }
+            }
+            // The RTC is always ready to take a new command
+            0x0D => 0x01,
+            0x0E => 0xC0 | self.infrared.light_detected() as u8,
+            _ => 0x01,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        match self.mode {
+            0x0A => {
+                if self.ram_banks.is_empty() {
+                    return;
+                }
+                let bank = self.current_ram_bank as usize % self.ram_banks.len();
+                self.ram_banks[bank][address as usize - 0xA000] = value;
+            }
+            0x0B => self.execute_command(value),
+            0x0E => self.infrared.set_led(value & 0x01 != 0),
+            _ => {}
+        }
+    }
+
+    fn attach_infrared(&mut self, port: Box<dyn InfraredPort>) {
+        self.infrared = port;
+    }
+
+    fn save_ram(&self) -> Vec<u8> {
+        dump_ram(&self.ram_banks)
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram_banks, data);
+    }
+
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::HuC3(HuC3State {
+            mode: self.mode,
+            current_ram_bank: self.current_ram_bank,
+            current_rom_bank: self.current_rom_bank,
+            minutes: self.minutes,
+            days: self.days,
+            seconds: self.seconds,
+            epoch: self.epoch,
+            access_index: self.access_index,
+            access_flags: self.access_flags,
+            read_value: self.read_value,
+            alarm_minutes: self.alarm_minutes,
+            alarm_days: self.alarm_days,
+            alarm_enabled: self.alarm_enabled,
+            ram: dump_ram(&self.ram_banks),
+        })
+    }
+
+    fn load_state(&mut self, state: CartridgeState) {
+        if let CartridgeState::HuC3(state) = state {
+            self.mode = state.mode;
+            self.current_ram_bank = state.current_ram_bank;
+            self.current_rom_bank = state.current_rom_bank;
+            self.minutes = state.minutes;
+            self.days = state.days;
+            self.seconds = state.seconds;
+            self.epoch = state.epoch;
+            self.access_index = state.access_index;
+            self.access_flags = state.access_flags;
+            self.read_value = state.read_value;
+            self.alarm_minutes = state.alarm_minutes;
+            self.alarm_days = state.alarm_days;
+            self.alarm_enabled = state.alarm_enabled;
+            restore_ram(&mut self.ram_banks, &state.ram);
+        }
+    }
+}
+
+const MBC7_EEPROM_SIZE: usize = 256;
+const MBC7_ACCELEROMETER_CENTER: u16 = 0x81D0;
+const MBC7_ACCELEROMETER_GRAVITY: f32 = 0x70 as f32;
+
+/// Progress of the serial transfer in flight on the MBC7 EEPROM.
+#[derive(Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EepromState {
+    Idle,
+    Command {
+        bits: u16,
+        count: u8,
+    },
+    Read {
+        address: u8,
+        data: u16,
+        count: u8,
+    },
+    Write {
+        address: u8,
+        data: u16,
+        count: u8,
+        all: bool,
+    },
+}
+
+/// 93LC56 serial EEPROM in its 128 x 16 bit organization.
+struct Eeprom {
+    data: [u8; MBC7_EEPROM_SIZE],
+    state: EepromState,
+    write_enabled: bool,
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Self {
+            data: [0xFF; MBC7_EEPROM_SIZE],
+            state: EepromState::Idle,
+            write_enabled: false,
+            chip_select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+        }
+    }
+
+    fn word(&self, address: u8) -> u16 {
+        let index = (address as usize & 0x7F) * 2;
+        u16::from_le_bytes([self.data[index], self.data[index + 1]])
+    }
+
+    fn set_word(&mut self, address: u8, value: u16) {
+        let index = (address as usize & 0x7F) * 2;
+        self.data[index..index + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn read_pins(&self) -> u8 {
+        (if self.chip_select { 0x80 } else { 0 })
+            | (if self.clock { 0x40 } else { 0 })
+            | (if self.data_in { 0x02 } else { 0 })
+            | self.data_out as u8
+    }
+
+    /// Updates the pins and returns true when the contents were modified.
+    fn write_pins(&mut self, value: u8) -> bool {
+        let chip_select = value & 0x80 != 0;
+        let clock = value & 0x40 != 0;
+        self.data_in = value & 0x02 != 0;
+
+        let mut modified = false;
+        if !chip_select {
+            self.state = EepromState::Idle;
+            self.data_out = true;
+        } else if self.chip_select && !self.clock && clock {
+            modified = self.clock_rising_edge();
+        }
+        self.chip_select = chip_select;
+        self.clock = clock;
+        modified
+    }
+
+    fn clock_rising_edge(&mut self) -> bool {
+        let bit = self.data_in as u16;
+        match self.state {
+            EepromState::Idle => {
+                if self.data_in {
+                    self.state = EepromState::Command { bits: 0, count: 0 };
+                }
+                false
+            }
+            EepromState::Command { bits, count } => {
+                let bits = (bits << 1) | bit;
+                if count + 1 < 10 {
+                    self.state = EepromState::Command {
+                        bits,
+                        count: count + 1,
+                    };
+                    return false;
+                }
+                self.execute((bits >> 8) as u8, bits as u8)
+            }
+            EepromState::Read {
+                address,
+                data,
+                count,
+            } => {
+                self.data_out = data & 0x8000 != 0;
+                self.state = if count + 1 < 16 {
+                    EepromState::Read {
+                        address,
+                        data: data << 1,
+                        count: count + 1,
+                    }
+                } else {
+                    // Sequential read carries on with the next word
+                    let next = address.wrapping_add(1) & 0x7F;
+                    EepromState::Read {
+                        address: next,
+                        data: self.word(next),
+                        count: 0,
+                    }
+                };
+                false
+            }
+            EepromState::Write {
+                address,
+                data,
+                count,
+                all,
+            } => {
+                let data = (data << 1) | bit;
+                if count + 1 < 16 {
+                    self.state = EepromState::Write {
+                        address,
+                        data,
+                        count: count + 1,
+                        all,
+                    };
+                    return false;
+                }
+                self.state = EepromState::Idle;
+                self.data_out = true;
+                if !self.write_enabled {
+                    return false;
+                }
+                if all {
+                    (0..0x80).for_each(|address| self.set_word(address, data));
+                } else {
+                    self.set_word(address, data);
+                }
+                true
+            }
+        }
+    }
+
+    fn execute(&mut self, opcode: u8, address: u8) -> bool {
+        self.state = EepromState::Idle;
+        match opcode & 0x03 {
+            0b10 => {
+                // A dummy zero bit precedes the data
+                self.data_out = false;
+                self.state = EepromState::Read {
+                    address: address & 0x7F,
+                    data: self.word(address),
+                    count: 0,
+                };
+                false
+            }
+            0b01 => {
+                self.data_out = false;
+                self.state = EepromState::Write {
+                    address: address & 0x7F,
+                    data: 0,
+                    count: 0,
+                    all: false,
+                };
+                false
+            }
+            0b11 => {
+                if self.write_enabled {
+                    self.set_word(address, 0xFFFF);
+                }
+                self.write_enabled
+            }
+            _ => match address >> 6 {
+                0b00 => {
+                    self.write_enabled = false;
+                    false
+                }
+                0b01 => {
+                    self.data_out = false;
+                    self.state = EepromState::Write {
+                        address: 0,
+                        data: 0,
+                        count: 0,
+                        all: true,
+                    };
+                    false
+                }
+                0b10 => {
+                    if self.write_enabled {
+                        self.data = [0xFF; MBC7_EEPROM_SIZE];
+                    }
+                    self.write_enabled
+                }
+                _ => {
+                    self.write_enabled = true;
+                    false
+                }
+            },
+        }
+    }
+}
+
+pub struct Mbc7Cartridge<RM: RomManager> {
+    rom_manager: Rom<RM>,
+    ram_enabled: bool,
+    ram_enabled_2: bool,
+    current_rom_bank: u8,
+    rom_banks: u16,
+    eeprom: Eeprom,
+
+    //
+    tilt_x: f32,
+    tilt_y: f32,
+    latch_ready: bool,
+    accelerometer_x: u16,
+    accelerometer_y: u16,
+}
+
+impl<RM: RomManager> Mbc7Cartridge<RM> {
+    pub fn new(rom_manager: Rom<RM>) -> Self {
+        let rom_banks = rom_manager.rom_size.banks();
+        let mut cartridge = Self {
+            rom_manager,
+            ram_enabled: false,
+            ram_enabled_2: false,
+            current_rom_bank: 1,
+            rom_banks,
+            eeprom: Eeprom::new(),
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            latch_ready: false,
+            accelerometer_x: 0x8000,
+            accelerometer_y: 0x8000,
+        };
+        cartridge.rom_manager.data.load_to_bank(
+            &cartridge.rom_manager.title,
+            0,
+            &mut cartridge.eeprom.data,
+        );
+        cartridge
+    }
+
+    fn latch_accelerometer(&mut self) {
+        let x = MBC7_ACCELEROMETER_CENTER as f32 + self.tilt_x * MBC7_ACCELEROMETER_GRAVITY;
+        let y = MBC7_ACCELEROMETER_CENTER as f32 + self.tilt_y * MBC7_ACCELEROMETER_GRAVITY;
+        self.accelerometer_x = x.clamp(0.0, u16::MAX as f32) as u16;
+        self.accelerometer_y = y.clamp(0.0, u16::MAX as f32) as u16;
+    }
+}
+
+impl<RM: RomManager> Cartridge for Mbc7Cartridge<RM> {
+    fn rom_bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
+        } else {
+            self.current_rom_bank as u16
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        if address >= 0x4000 {
+            let bank_offset = self.current_rom_bank as usize * (0x7FFF - 0x4000 + 1);
+            return self.rom_manager.data.read_from_offset(
+                bank_offset,
+                (address - 0x4000) as usize,
+                self.current_rom_bank as u16,
+            );
+        }
+        self.rom_manager
+            .data
+            .read_from_offset(0x0000, address as usize, 0)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        if address < 0x2000 {
+            self.ram_enabled = data == 0x0A;
+        } else if address < 0x4000 {
+            self.current_rom_bank = ((data & 0x7F) as u16 % self.rom_banks.max(2)) as u8;
+        } else if address < 0x6000 {
+            self.ram_enabled_2 = data == 0x40;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || !self.ram_enabled_2 || address >= 0xB000 {
+            return 0xFF;
+        }
+        match (address >> 4) & 0x0F {
+            0x2 => self.accelerometer_x as u8,
+            0x3 => (self.accelerometer_x >> 8) as u8,
+            0x4 => self.accelerometer_y as u8,
+            0x5 => (self.accelerometer_y >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read_pins(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled || !self.ram_enabled_2 || address >= 0xB000 {
+            return;
+        }
+        match (address >> 4) & 0x0F {
+            0x0 if value == 0x55 => {
+                self.latch_ready = true;
+                self.accelerometer_x = 0x8000;
+                self.accelerometer_y = 0x8000;
+            }
+            0x1 if value == 0xAA && self.latch_ready => {
+                self.latch_ready = false;
+                self.latch_accelerometer();
+            }
+            0x8 => {
+                let modified = self.eeprom.write_pins(value);
+                if modified {
+                    self.rom_manager
+                        .data
+                        .save(&self.rom_manager.title, 0, &self.eeprom.data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt_x = x;
+        self.tilt_y = y;
+    }
+
+    fn save_ram(&self) -> Vec<u8> {
+        self.eeprom.data.to_vec()
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        let length = data.len().min(MBC7_EEPROM_SIZE);
+        self.eeprom.data[..length].copy_from_slice(&data[..length]);
+    }
+
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::Mbc7(Mbc7State {
+            ram_enabled: self.ram_enabled,
+            ram_enabled_2: self.ram_enabled_2,
+            current_rom_bank: self.current_rom_bank,
+            latch_ready: self.latch_ready,
+            accelerometer_x: self.accelerometer_x,
+            accelerometer_y: self.accelerometer_y,
+            eeprom: self.eeprom.data.to_vec(),
+            eeprom_state: self.eeprom.state,
+            eeprom_write_enabled: self.eeprom.write_enabled,
+            eeprom_chip_select: self.eeprom.chip_select,
+            eeprom_clock: self.eeprom.clock,
+            eeprom_data_in: self.eeprom.data_in,
+            eeprom_data_out: self.eeprom.data_out,
+        })
+    }
+
+    fn load_state(&mut self, state: CartridgeState) {
+        if let CartridgeState::Mbc7(state) = state {
+            self.ram_enabled = state.ram_enabled;
+            self.ram_enabled_2 = state.ram_enabled_2;
+            self.current_rom_bank = state.current_rom_bank;
+            self.latch_ready = state.latch_ready;
+            self.accelerometer_x = state.accelerometer_x;
+            self.accelerometer_y = state.accelerometer_y;
+            let length = state.eeprom.len().min(MBC7_EEPROM_SIZE);
+            self.eeprom.data[..length].copy_from_slice(&state.eeprom[..length]);
+            self.eeprom.state = state.eeprom_state;
+            self.eeprom.write_enabled = state.eeprom_write_enabled;
+            self.eeprom.chip_select = state.eeprom_chip_select;
+            self.eeprom.clock = state.eeprom_clock;
+            self.eeprom.data_in = state.eeprom_data_in;
+            self.eeprom.data_out = state.eeprom_data_out;
+        }
+    }
+}
+
+const CAMERA_REGISTER_COUNT: usize = 0x36;
+const CAMERA_DITHER_MATRIX: usize = 0x06;
+const CAMERA_IMAGE_OFFSET: usize = 0x0100;
+const CAMERA_GAIN: [f32; 32] = [
+    0.880939, 0.914915, 0.94575, 0.973976, 1.0, 1.024141, 1.046654, 1.067743, 1.087579, 1.124031,
+    1.156891, 1.186804, 1.214256, 1.239621, 1.274384, 1.315732, 1.352519, 1.385651, 1.41579,
+    1.443431, 1.468957, 1.49267, 1.514809, 1.53557, 1.555116, 1.57358, 1.591076, 1.607701,
+    1.623537, 1.638655, 1.653118, 1.666981,
+];
+const CAMERA_EDGE_RATIO: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+pub struct PocketCameraCartridge<RM: RomManager> {
+    rom_manager: Rom<RM>,
+    ram_enabled: bool,
+    ram_banks: Box<[[u8; 0xBFFF - 0xA000 + 1]]>,
+    current_ram_bank: u8,
+    registers_mapped: bool,
+    current_rom_bank: u8,
+    rom_banks: u16,
+    registers: [u8; CAMERA_REGISTER_COUNT],
+    capture_cycles: u32,
+    frame: Box<CameraFrame>,
+    sensor: Box<dyn CameraSensor>,
+}
+
+impl<RM: RomManager> PocketCameraCartridge<RM> {
+    pub fn new(rom_manager: Rom<RM>) -> Self {
+        let banks = rom_manager.ram_size.banks();
+        let rom_banks = rom_manager.rom_size.banks();
+        let mut cartridge = Self {
+            rom_manager,
+            ram_enabled: false,
+            ram_banks: (0..banks).map(|_| [0; 0xBFFF - 0xA000 + 1]).collect(),
+            current_ram_bank: 0,
+            registers_mapped: false,
+            current_rom_bank: 1,
+            rom_banks,
+            registers: [0; CAMERA_REGISTER_COUNT],
+            capture_cycles: 0,
+            frame: Box::new([0; CAMERA_PIXELS]),
+            sensor: Box::new(NullCamera),
+        };
+        for (index, bank) in &mut cartridge.ram_banks.iter_mut().enumerate() {
+            cartridge.rom_manager.data.load_to_bank(
+                &cartridge.rom_manager.title,
+                index as u8,
+                bank,
+            );
+        }
+        cartridge
+    }
+
+    fn flush_banks(&mut self) {
+        for (index, bank) in self.ram_banks.iter().enumerate() {
+            self.rom_manager
+                .data
+                .save(&self.rom_manager.title, index as u8, bank);
+        }
+    }
+
+    fn exposure(&self) -> u16 {
+        ((self.registers[2] as u16) << 8) | self.registers[3] as u16
+    }
+
+    fn start_capture(&mut self) {
+        // Duration is given in M-cycles, the N bit skips part of the readout
+        let n_bit = self.registers[1] & 0x80 != 0;
+        let m_cycles = 32446 + if n_bit { 0 } else { 512 } + 16 * self.exposure() as u32;
+        self.capture_cycles = m_cycles * 4;
+        self.sensor.capture(&mut self.frame);
+    }
+
+    fn processed_pixel(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, CAMERA_WIDTH as isize - 1) as usize;
+        let y = y.clamp(0, CAMERA_HEIGHT as isize - 1) as usize;
+        let mut pixel = self.frame[y * CAMERA_WIDTH + x];
+        if self.registers[4] & 0x08 != 0 {
+            pixel = !pixel;
+        }
+        let gain = CAMERA_GAIN[(self.registers[1] & 0x1F) as usize];
+        pixel as f32 * gain * self.exposure() as f32 / 0x1000 as f32
+    }
+
+    fn finish_capture(&mut self) {
+        let edge_enhancement = self.registers[1] & 0xE0 == 0xE0;
+        let edge_ratio = CAMERA_EDGE_RATIO[((self.registers[4] >> 4) & 0x07) as usize];
+
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let (px, py) = (x as isize, y as isize);
+                let mut color = self.processed_pixel(px, py);
+                if edge_enhancement {
+                    color += color * 4.0 * edge_ratio;
+                    color -= self.processed_pixel(px - 1, py) * edge_ratio;
+                    color -= self.processed_pixel(px + 1, py) * edge_ratio;
+                    color -= self.processed_pixel(px, py - 1) * edge_ratio;
+                    color -= self.processed_pixel(px, py + 1) * edge_ratio;
+                }
+
+                // Every pixel is compared against the 3 thresholds of its 4x4 matrix cell
+                let matrix = CAMERA_DITHER_MATRIX + ((x & 3) + (y & 3) * 4) * 3;
+                let shade: u8 = if color < self.registers[matrix] as f32 {
+                    3
+                } else if color < self.registers[matrix + 1] as f32 {
+                    2
+                } else if color < self.registers[matrix + 2] as f32 {
+                    1
+                } else {
+                    0
+                };
+
+                let tile = (y / 8) * (CAMERA_WIDTH / 8) + x / 8;
+                let index = CAMERA_IMAGE_OFFSET + tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                let bank = &mut self.ram_banks[0];
+                bank[index] = (bank[index] & !(1 << bit)) | ((shade & 0x01) << bit);
+                bank[index + 1] = (bank[index + 1] & !(1 << bit)) | (((shade >> 1) & 0x01) << bit);
+            }
+        }
+        self.registers[0] &= !0x01;
+    }
+}
+
+impl<RM: RomManager> Cartridge for PocketCameraCartridge<RM> {
+    fn rom_bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
+        } else {
+            self.current_rom_bank as u16
+        }
+    }
+
+    fn step(&mut self, cycles: u8) {
+        if self.capture_cycles == 0 {
+            return;
+        }
+        self.capture_cycles = self.capture_cycles.saturating_sub(cycles as u32);
+        if self.capture_cycles == 0 && !self.ram_banks.is_empty() {
+            self.finish_capture();
+        }
+    }
+
+    fn read_rom(&self, address: u16) -> u8 {
+        if address >= 0x4000 {
+            let bank_offset = self.current_rom_bank as usize * (0x7FFF - 0x4000 + 1);
+            return self.rom_manager.data.read_from_offset(
+                bank_offset,
+                (address - 0x4000) as usize,
+                self.current_rom_bank as u16,
+            );
+        }
+        self.rom_manager
+            .data
+            .read_from_offset(0x0000, address as usize, 0)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        if address < 0x2000 {
+            let current_state = self.ram_enabled;
+            self.ram_enabled = data & 0x0F == 0x0A;
+            if current_state && !self.ram_enabled {
+                self.flush_banks();
+            }
+        } else if address < 0x4000 {
+            self.current_rom_bank = ((data & 0x3F) as u16 % self.rom_banks.max(2)) as u8;
+        } else if address < 0x6000 {
+            self.registers_mapped = data & 0x10 != 0;
+            self.current_ram_bank = data & 0x0F;
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.registers_mapped {
+            // Only the capture status can be read back
+            return if address & 0x7F == 0 {
+                self.registers[0]
+            } else {
+                0x00
+            };
+        }
+        if self.capture_cycles > 0 || self.ram_banks.is_empty() {
+            return 0x00;
+        }
+        let bank = self.current_ram_bank as usize % self.ram_banks.len();
+        self.ram_banks[bank][address as usize - 0xA000]
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.registers_mapped {
+            let register = (address & 0x7F) as usize;
+            if register == 0 {
+                let start = value & 0x01 != 0 && self.capture_cycles == 0;
+                self.registers[0] = value & 0x07;
+                if start {
+                    self.start_capture();
+                } else if value & 0x01 == 0 {
+                    // Clearing the trigger aborts a running capture
+                    self.capture_cycles = 0;
+                }
+            } else if register < CAMERA_REGISTER_COUNT {
+                self.registers[register] = value;
+            }
+            return;
+        }
+        if !self.ram_enabled || self.capture_cycles > 0 || self.ram_banks.is_empty() {
+            return;
+        }
+        let bank = self.current_ram_bank as usize % self.ram_banks.len();
+        self.ram_banks[bank][address as usize - 0xA000] = value;
+    }
+
+    fn attach_camera(&mut self, sensor: Box<dyn CameraSensor>) {
+        self.sensor = sensor;
+    }
+
+    fn save_ram(&self) -> Vec<u8> {
+        dump_ram(&self.ram_banks)
+    }
+
+    fn load_ram(&mut self, data: &[u8]) {
+        restore_ram(&mut self.ram_banks, data);
+    }
+
+    fn create_state(&self) -> CartridgeState {
+        CartridgeState::PocketCamera(PocketCameraState {
+            ram_enabled: self.ram_enabled,
+            current_ram_bank: self.current_ram_bank,
+            registers_mapped: self.registers_mapped,
+            current_rom_bank: self.current_rom_bank,
+            registers: self.registers,
+            capture_cycles: self.capture_cycles,
+            frame: self.frame.to_vec(),
+            ram: dump_ram(&self.ram_banks),
+        })
+    }
+
+    fn load_state(&mut self, state: CartridgeState) {
+        if let CartridgeState::PocketCamera(state) = state {
+            self.ram_enabled = state.ram_enabled;
+            self.current_ram_bank = state.current_ram_bank;
+            self.registers_mapped = state.registers_mapped;
+            self.current_rom_bank = state.current_rom_bank;
+            self.registers = state.registers;
+            self.capture_cycles = state.capture_cycles;
+            let length = state.frame.len().min(CAMERA_PIXELS);
+            self.frame[..length].copy_from_slice(&state.frame[..length]);
+            restore_ram(&mut self.ram_banks, &state.ram);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{rom, TestRom};
+
+    #[test]
+    fn huc1_flushes_banks_when_writes_are_disabled() {
+        // HuC1 with 32KB of RAM
+        let mut cartridge = HuC1Cartridge::new(rom(0xFF, 0x01, 0x03, &[]));
+        for _ in 0..3 {
+            cartridge.write_rom(0x0000, 0x0E);
+            cartridge.write_rom(0x0000, 0x00);
+        }
+        assert_eq!(cartridge.rom_manager.data.saves, 0);
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x12);
+        cartridge.write_rom(0x0000, 0x0A);
+        assert_eq!(cartridge.rom_manager.data.saves, 0);
+        cartridge.write_rom(0x0000, 0x0E);
+        assert_eq!(cartridge.rom_manager.data.saves, 4);
+        cartridge.write_rom(0x0000, 0x00);
+        assert_eq!(cartridge.rom_manager.data.saves, 4);
+    }
+
+    fn rtc(seconds: u8, minutes: u8, hours: u8, days: u16) -> Rtc {
+        let mut rtc = Rtc::new();
+        rtc.seconds = seconds;
+        rtc.minutes = minutes;
+        rtc.hours = hours;
+        rtc.days = days;
+        rtc
+    }
+
+    fn run_seconds(rtc: &mut Rtc, seconds: u32) {
+        for _ in 0..seconds * (RTC_CYCLES_PER_SECOND / 128) {
+            rtc.step(128);
+        }
+    }
+
+    #[test]
+    fn rtc_tick_rolls_over() {
+        let mut clock = rtc(59, 59, 23, 0x0FF);
+        clock.tick();
+        assert_eq!(clock.registers(), [0, 0, 0, 0x00, 0x01]);
+        assert!(!clock.carry);
+
+        let mut clock = rtc(58, 59, 23, 0x1FF);
+        run_seconds(&mut clock, 2);
+        assert_eq!(clock.registers(), [0, 0, 0, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn rtc_out_of_range_values_wrap_at_the_register_width() {
+        let mut clock = rtc(62, 60, 24, 0);
+        clock.tick();
+        assert_eq!(clock.registers(), [63, 60, 24, 0, 0]);
+        clock.tick();
+        assert_eq!(clock.registers(), [0, 60, 24, 0, 0]);
+    }
+
+    #[test]
+    fn rtc_advance_matches_ticking() {
+        let starts = [
+            rtc(0, 0, 0, 0),
+            rtc(59, 59, 23, 0x1FF),
+            rtc(61, 63, 31, 0x0FF),
+            rtc(30, 61, 25, 0x1FE),
+        ];
+        let amounts = [
+            0,
+            1,
+            59,
+            60,
+            3599,
+            3600,
+            86_399,
+            86_400,
+            90_061,
+            200 * 86_400,
+        ];
+        for start in starts.iter() {
+            for amount in amounts {
+                let mut advanced = rtc(start.seconds, start.minutes, start.hours, start.days);
+                let mut ticked = rtc(start.seconds, start.minutes, start.hours, start.days);
+                advanced.advance(amount);
+                for _ in 0..amount {
+                    ticked.tick();
+                }
+                assert_eq!(
+                    advanced.registers(),
+                    ticked.registers(),
+                    "{} seconds",
+                    amount
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rtc_day_counter_has_nine_bits_and_a_sticky_carry() {
+        let mut clock = rtc(0, 0, 0, 0x0FF);
+        clock.advance(86_400);
+        assert_eq!(clock.registers()[3..], [0x00, 0x01]);
+
+        clock.advance(0x100 * 86_400);
+        assert_eq!(clock.registers()[3..], [0x00, 0x80]);
+        clock.advance(86_400);
+        assert_eq!(clock.registers()[3..], [0x01, 0x80]);
+
+        clock.write(0x0c, 0x00);
+        assert!(!clock.carry);
+        assert_eq!(clock.days, 0x01);
+    }
+
+    #[test]
+    fn rtc_halt_stops_the_clock() {
+        let mut clock = rtc(10, 0, 0, 0);
+        clock.write(0x0c, 0x40);
+        run_seconds(&mut clock, 3);
+        assert_eq!(clock.seconds, 10);
+
+        clock.host_time = 1000;
+        clock.sync(5000);
+        assert_eq!(clock.seconds, 10);
+        assert_eq!(clock.host_time, 5000);
+
+        clock.write(0x0c, 0x00);
+        clock.sync(5002);
+        assert_eq!(clock.seconds, 12);
+    }
+
+    #[test]
+    fn rtc_seconds_write_resets_the_prescaler() {
+        let mut clock = rtc(0, 0, 0, 0);
+        for _ in 0..(RTC_CYCLES_PER_SECOND - 128) / 128 {
+            clock.step(128);
+        }
+        clock.write(0x08, 5);
+        clock.step(128);
+        assert_eq!(clock.seconds, 5);
+        for _ in 0..(RTC_CYCLES_PER_SECOND - 256) / 128 {
+            clock.step(128);
+        }
+        assert_eq!(clock.seconds, 5);
+        clock.step(128);
+        assert_eq!(clock.seconds, 6);
+    }
+
+    /// MBC3 with a clock, 64KB of ROM and 32KB of RAM, ready to access the clock.
+    fn mbc3_timer() -> Mbc3Cartridge<TestRom> {
+        let mut cartridge = Mbc3Cartridge::new(rom(0x10, 0x01, 0x03, &[]));
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x4000, 0x08);
+        cartridge
+    }
+
+    fn latch(cartridge: &mut Mbc3Cartridge<TestRom>, values: &[u8]) {
+        for value in values {
+            cartridge.write_rom(0x6000, *value);
+        }
+    }
+
+    #[test]
+    fn rtc_latches_on_zero_then_one() {
+        let mut cartridge = mbc3_timer();
+        latch(&mut cartridge, &[0x00, 0x01]);
+        assert_eq!(cartridge.read_ram(0xA000), 0);
+
+        cartridge.rtc.advance(5);
+        // Reads keep the latched value
+        assert_eq!(cartridge.read_ram(0xA000), 0);
+        // A one without a zero before it does not latch
+        latch(&mut cartridge, &[0x01]);
+        assert_eq!(cartridge.read_ram(0xA000), 0);
+        // Neither does any other sequence
+        latch(&mut cartridge, &[0x00, 0x02, 0x01]);
+        assert_eq!(cartridge.read_ram(0xA000), 0);
+        latch(&mut cartridge, &[0x00, 0x01]);
+        assert_eq!(cartridge.read_ram(0xA000), 5);
+    }
+
+    #[test]
+    fn rtc_writes_show_in_the_latched_registers() {
+        let mut cartridge = mbc3_timer();
+        cartridge.write_ram(0xA000, 0xFF);
+        assert_eq!(cartridge.read_ram(0xA000), 0x3F);
+        cartridge.write_rom(0x4000, 0x0C);
+        cartridge.write_ram(0xA000, 0xFF);
+        assert_eq!(cartridge.read_ram(0xA000), 0xC1);
+    }
+
+    #[test]
+    fn host_sync_catches_up_with_the_time_spent_closed() {
+        let mut cartridge = mbc3_timer();
+        let now = 1_700_000_000;
+        cartridge.rom_manager.data.clock = now * 1_000_000;
+        cartridge.set_rtc_host_sync(true);
+        // Saved with the clock at zero a day, an hour, a minute and a second ago
+        let mut data = vec![0; 4 * RAM_BANK_SIZE + 40];
+        data.extend_from_slice(&(now - 90_061).to_le_bytes());
+        cartridge.load_ram(&data);
+        assert_eq!(cartridge.rtc.registers(), [1, 1, 1, 1, 0]);
+
+        cartridge.rom_manager.data.clock += 2_000_000;
+        for _ in 0..RTC_CYCLES_PER_SECOND / 128 {
+            cartridge.step(128);
+        }
+        assert_eq!(cartridge.rtc.seconds, 3);
+    }
+
+    #[test]
+    fn sav_footer_uses_the_shared_layout() {
+        let mut cartridge = mbc3_timer();
+        cartridge.rom_manager.data.clock = 1_700_000_000_123_456;
+        cartridge.write_ram(0xA000, 30);
+        latch(&mut cartridge, &[0x00, 0x01]);
+        cartridge.write_rom(0x4000, 0x09);
+        cartridge.write_ram(0xA000, 15);
+
+        let data = cartridge.save_ram();
+        assert_eq!(data.len(), 4 * RAM_BANK_SIZE + RTC_FOOTER_SIZE);
+        let footer = &data[4 * RAM_BANK_SIZE..];
+        let word =
+            |index: usize| u32::from_le_bytes(footer[index * 4..index * 4 + 4].try_into().unwrap());
+        assert_eq!((0..5).map(word).collect::<Vec<_>>(), [30, 15, 0, 0, 0]);
+        assert_eq!((5..10).map(word).collect::<Vec<_>>(), [30, 15, 0, 0, 0]);
+        assert_eq!(footer[40..], 1_700_000_000u64.to_le_bytes());
+
+        let mut restored = mbc3_timer();
+        restored.load_ram(&data);
+        assert_eq!(restored.rtc.registers(), [30, 15, 0, 0, 0]);
+        assert_eq!(
+            restored.save_ram()[..4 * RAM_BANK_SIZE + 40],
+            data[..4 * RAM_BANK_SIZE + 40]
+        );
+    }
+
+    /// MBC3 without a clock with RAM writes enabled.
+    fn mbc3(rom_size: u8, ram_size: u8) -> Mbc3Cartridge<TestRom> {
+        let mut cartridge = Mbc3Cartridge::new(rom(0x13, rom_size, ram_size, &[]));
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge
+    }
+
+    #[test]
+    fn mbc3_maps_rom_bank_0_to_1() {
+        let mut cartridge = mbc3(0x02, 0x00);
+        assert_eq!(cartridge.read_rom(0x4000), 1);
+        cartridge.write_rom(0x2000, 0x03);
+        assert_eq!(cartridge.read_rom(0x4000), 3);
+        cartridge.write_rom(0x2000, 0x00);
+        assert_eq!(cartridge.read_rom(0x4000), 1);
+        // Bit 7 is not decoded, 0x80 selects bank 0 as well
+        cartridge.write_rom(0x2000, 0x80);
+        assert_eq!(cartridge.read_rom(0x4000), 1);
+        assert_eq!(cartridge.read_rom(0x0000), 0);
+    }
+
+    #[test]
+    fn mbc3_unmapped_selectors_are_open_bus() {
+        let mut cartridge = mbc3(0x01, 0x03);
+        for bank in 0..4 {
+            cartridge.write_rom(0x4000, bank);
+            cartridge.write_ram(0xA000, 0x10 + bank);
+        }
+        // 0x04-0x07 are only wired on MBC30 and 0x08-0x0C need a clock
+        for selector in [0x04, 0x07, 0x08, 0x0C, 0x0D, 0xFF] {
+            cartridge.write_rom(0x4000, selector);
+            cartridge.write_ram(0xA000, 0x55);
+            assert_eq!(
+                cartridge.read_ram(0xA000),
+                0xFF,
+                "selector {:#04x}",
+                selector
+            );
+        }
+        for bank in 0..4 {
+            cartridge.write_rom(0x4000, bank);
+            assert_eq!(cartridge.read_ram(0xA000), 0x10 + bank);
+        }
+        assert_eq!(cartridge.rtc.registers(), [0; 5]);
+
+        cartridge.write_rom(0x0000, 0x00);
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn mbc3_clock_selectors_need_a_timer() {
+        let mut cartridge = mbc3_timer();
+        cartridge.write_ram(0xA000, 42);
+        assert_eq!(cartridge.read_ram(0xA000), 42);
+
+        let mut cartridge = mbc3(0x01, 0x03);
+        cartridge.write_rom(0x4000, 0x08);
+        cartridge.write_ram(0xA000, 42);
+        latch(&mut cartridge, &[0x00, 0x01]);
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+        assert_eq!(cartridge.rtc.seconds, 0);
+        assert_eq!(cartridge.save_ram().len(), 4 * RAM_BANK_SIZE);
+    }
+
+    #[test]
+    fn mbc30_decodes_eight_ram_banks_and_eight_rom_bits() {
+        let mut cartridge = mbc3(0x07, 0x05);
+        assert!(cartridge.mbc30);
+        for bank in 0..8 {
+            cartridge.write_rom(0x4000, bank);
+            cartridge.write_ram(0xA000, 0x20 + bank);
+        }
+        for bank in 0..8 {
+            cartridge.write_rom(0x4000, bank);
+            assert_eq!(cartridge.read_ram(0xA000), 0x20 + bank);
+        }
+        cartridge.write_rom(0x2000, 0xFF);
+        assert_eq!(cartridge.read_rom(0x4000), 0xFF);
+        cartridge.write_rom(0x2000, 0x80);
+        assert_eq!(cartridge.read_rom(0x4000), 0x80);
+
+        // A plain MBC3 masks the bank to 7 bits and has 4 RAM banks
+        let mut cartridge = mbc3(0x06, 0x03);
+        assert!(!cartridge.mbc30);
+        cartridge.write_rom(0x2000, 0x85);
+        assert_eq!(cartridge.read_rom(0x4000), 0x05);
+        cartridge.write_rom(0x4000, 0x04);
+        cartridge.write_ram(0xA000, 0x24);
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+    }
+
+    /// Pocket Camera with registers mapped, unit gain and exposure and the same three
+    /// thresholds in every dither matrix cell.
+    fn camera() -> PocketCameraCartridge<TestRom> {
+        let mut cartridge = PocketCameraCartridge::new(rom(0xFC, 0x00, 0x04, &[]));
+        cartridge.write_rom(0x4000, 0x10);
+        cartridge.write_ram(0xA001, 0x04);
+        cartridge.write_ram(0xA002, 0x10);
+        cartridge.write_ram(0xA003, 0x00);
+        for cell in 0..16 {
+            for (threshold, value) in [0x40, 0x80, 0xC0].into_iter().enumerate() {
+                cartridge.write_ram(0xA006 + cell * 3 + threshold as u16, value);
+            }
+        }
+        cartridge
+    }
+
+    /// Four vertical stripes, one per shade from black to white.
+    fn stripes(frame: &mut CameraFrame) {
+        for (index, pixel) in frame.iter_mut().enumerate() {
+            *pixel = [0x00, 0x60, 0xA0, 0xFF][index % CAMERA_WIDTH / 32];
+        }
+    }
+
+    fn run_capture(cartridge: &mut PocketCameraCartridge<TestRom>) {
+        while cartridge.read_ram(0xA000) & 0x01 != 0 {
+            cartridge.step(255);
+        }
+    }
+
+    /// Both bitplanes of the first row of each stripe's first tile.
+    fn stripe_rows(cartridge: &mut PocketCameraCartridge<TestRom>) -> Vec<(u8, u8)> {
+        cartridge.write_rom(0x4000, 0x00);
+        (0..4)
+            .map(|stripe| {
+                let address = 0xA000 + CAMERA_IMAGE_OFFSET as u16 + stripe * 4 * 16;
+                (cartridge.read_ram(address), cartridge.read_ram(address + 1))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn camera_captures_the_sensor_picture() {
+        let mut cartridge = camera();
+        cartridge.attach_camera(Box::new(stripes));
+        cartridge.write_ram(0xA000, 0x01);
+        assert_eq!(cartridge.read_ram(0xA000), 0x01);
+        cartridge.step(255);
+        assert_eq!(cartridge.read_ram(0xA000), 0x01);
+
+        run_capture(&mut cartridge);
+        assert_eq!(cartridge.read_ram(0xA000), 0x00);
+        assert_eq!(
+            stripe_rows(&mut cartridge),
+            [(0xFF, 0xFF), (0x00, 0xFF), (0xFF, 0x00), (0x00, 0x00)]
+        );
+        // The last row of the last tile is white
+        assert_eq!(
+            cartridge.read_ram(0xA000 + CAMERA_IMAGE_OFFSET as u16 + 224 * 16 - 2),
+            0x00
+        );
+    }
+
+    #[test]
+    fn camera_state_keeps_the_picture_being_captured() {
+        let mut cartridge = camera();
+        cartridge.attach_camera(Box::new(stripes));
+        cartridge.write_ram(0xA000, 0x01);
+        cartridge.step(255);
+        let state = cartridge.create_state();
+
+        let mut restored = camera();
+        restored.load_state(state);
+        assert_eq!(restored.read_ram(0xA000), 0x01);
+        run_capture(&mut restored);
+        assert_eq!(
+            stripe_rows(&mut restored),
+            [(0xFF, 0xFF), (0x00, 0xFF), (0xFF, 0x00), (0x00, 0x00)]
+        );
+    }
+
+    fn mbc1(rom_size: u8) -> Mbc1Cartridge<TestRom> {
+        let rom = rom(0x03, rom_size, 0x03, &[]);
+        let rom_banks = rom.rom_size.banks();
+        let mut cartridge = Mbc1Cartridge::new(rom.data, 4, rom_banks, true);
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge
+    }
+
+    /// Banks read at 0x0000 and 0x4000, with the bank reported to the debugger.
+    fn mbc1_banks(cartridge: &Mbc1Cartridge<TestRom>) -> (u8, u8) {
+        let low = cartridge.read_rom(0x0000);
+        let high = cartridge.read_rom(0x4000);
+        assert_eq!(cartridge.rom_bank(0x0000), low as u16);
+        assert_eq!(cartridge.rom_bank(0x4000), high as u16);
+        (low, high)
+    }
+
+    #[test]
+    fn mbc1_bank_0_maps_to_the_next_bank() {
+        let mut cartridge = mbc1(0x06);
+        for (written, bank) in [(0x00, 0x01), (0x01, 0x01), (0x1F, 0x1F), (0x20, 0x01)] {
+            cartridge.write_rom(0x2000, written);
+            assert_eq!(mbc1_banks(&cartridge), (0x00, bank));
+        }
+        // 0x20, 0x40 and 0x60 cannot be mapped at 0x4000
+        for (upper, bank) in [(1, 0x21), (2, 0x41), (3, 0x61)] {
+            cartridge.write_rom(0x2000, 0x00);
+            cartridge.write_rom(0x4000, upper);
+            assert_eq!(mbc1_banks(&cartridge), (0x00, bank));
+        }
+    }
+
+    #[test]
+    fn mbc1_mode_1_maps_the_upper_bits_to_bank_0_and_ram() {
+        let mut cartridge = mbc1(0x06);
+        cartridge.write_rom(0x2000, 0x02);
+        cartridge.write_rom(0x6000, 0x01);
+        for upper in 0..4 {
+            cartridge.write_rom(0x4000, upper);
+            assert_eq!(mbc1_banks(&cartridge), (upper << 5, (upper << 5) | 0x02));
+            cartridge.write_ram(0xA000, 0x30 + upper);
+        }
+        for upper in 0..4 {
+            cartridge.write_rom(0x4000, upper);
+            assert_eq!(cartridge.read_ram(0xA000), 0x30 + upper);
+        }
+
+        // Mode 0 only sees RAM bank 0
+        cartridge.write_rom(0x6000, 0x00);
+        assert_eq!(cartridge.read_ram(0xA000), 0x30);
+        assert_eq!(mbc1_banks(&cartridge), (0x00, 0x62));
+
+        // Smaller ROMs ignore the bits they do not have
+        let mut cartridge = mbc1(0x04);
+        cartridge.write_rom(0x6000, 0x01);
+        cartridge.write_rom(0x4000, 0x01);
+        cartridge.write_rom(0x2000, 0x03);
+        assert_eq!(mbc1_banks(&cartridge), (0x00, 0x03));
+    }
+
+    #[test]
+    fn mbc1m_is_detected_from_the_extra_logos() {
+        let mut rom = rom(0x01, 0x05, 0x00, &[]);
+        for game in 0..4 {
+            let offset = game * MBC1M_GAME_SIZE + NINTENDO_LOGO_OFFSET;
+            rom.data.data[offset..offset + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        }
+        let mut cartridge = Mbc1Cartridge::new(rom.data, 0, MBC1M_ROM_BANKS, false);
+        assert!(cartridge.multicart);
+        cartridge.write_rom(0x6000, 0x01);
+        cartridge.write_rom(0x4000, 0x01);
+        cartridge.write_rom(0x2000, 0x12);
+        assert_eq!(mbc1_banks(&cartridge), (0x10, 0x12));
+        cartridge.write_rom(0x2000, 0x10);
+        assert_eq!(mbc1_banks(&cartridge), (0x10, 0x10));
+
+        // A 1 MiB cart with a single logo is a regular MBC1
+        let mut cartridge = mbc1(0x05);
+        assert!(!cartridge.multicart);
+        cartridge.write_rom(0x4000, 0x01);
+        cartridge.write_rom(0x2000, 0x12);
+        assert_eq!(mbc1_banks(&cartridge), (0x00, 0x32));
+    }
+
+    #[test]
+    fn mbc5_selects_nine_bit_banks_and_bank_0() {
+        // 8MB, the largest MBC5 ROM
+        let mut large = rom(0x1B, 0x08, 0x03, &[]);
+        large.data.data[0x101 * 0x4000] = 0xAB;
+        let mut cartridge = Mbc5Cartridge::new(large);
+        assert_eq!(cartridge.read_rom(0x4000), 0x01);
+
+        cartridge.write_rom(0x2000, 0x01);
+        cartridge.write_rom(0x3000, 0x01);
+        assert_eq!(cartridge.rom_bank(0x4000), 0x101);
+        assert_eq!(cartridge.read_rom(0x4000), 0xAB);
+        cartridge.write_rom(0x2000, 0xFF);
+        assert_eq!(cartridge.rom_bank(0x4000), 0x1FF);
+        // Only bit 0 of the high register is wired
+        cartridge.write_rom(0x3000, 0xFE);
+        assert_eq!(cartridge.rom_bank(0x4000), 0x0FF);
+
+        cartridge.write_rom(0x2000, 0x00);
+        assert_eq!(cartridge.rom_bank(0x4000), 0);
+        assert_eq!(cartridge.read_rom(0x4000), cartridge.read_rom(0x0000));
+
+        // Smaller ROMs wrap
+        let mut cartridge = Mbc5Cartridge::new(rom(0x1B, 0x02, 0x03, &[]));
+        cartridge.write_rom(0x2000, 0x09);
+        assert_eq!(cartridge.read_rom(0x4000), 0x01);
+    }
+
+    #[test]
+    fn mbc5_rumble_takes_bit_3_of_the_ram_bank() {
+        let mut cartridge = Mbc5Cartridge::new(rom(0x1E, 0x01, 0x03, &[]));
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x4000, 0x01);
+        cartridge.write_ram(0xA000, 0x11);
+        cartridge.write_rom(0x4000, 0x09);
+        assert!(cartridge.rumble());
+        assert_eq!(cartridge.read_ram(0xA000), 0x11);
+        cartridge.write_rom(0x4000, 0x01);
+        assert!(!cartridge.rumble());
+
+        // Without a motor bit 3 selects RAM banks 8 to 15
+        let mut cartridge = Mbc5Cartridge::new(rom(0x1B, 0x01, 0x04, &[]));
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x4000, 0x01);
+        cartridge.write_ram(0xA000, 0x11);
+        cartridge.write_rom(0x4000, 0x09);
+        cartridge.write_ram(0xA000, 0x99);
+        assert!(!cartridge.rumble());
+        cartridge.write_rom(0x4000, 0x01);
+        assert_eq!(cartridge.read_ram(0xA000), 0x11);
+        cartridge.write_rom(0x4000, 0x09);
+        assert_eq!(cartridge.read_ram(0xA000), 0x99);
+    }
+
+    fn mbc2() -> Mbc2Cartridge<TestRom> {
+        let mut cartridge = Mbc2Cartridge::new(rom(0x06, 0x03, 0x00, &[]));
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge
+    }
+
+    #[test]
+    fn mbc2_ram_has_four_bits() {
+        let mut cartridge = mbc2();
+        cartridge.write_ram(0xA000, 0xA5);
+        assert_eq!(cartridge.read_ram(0xA000), 0xF5);
+        cartridge.write_ram(0xA1FF, 0x0C);
+        assert_eq!(cartridge.read_ram(0xA1FF), 0xFC);
+        assert_eq!(cartridge.save_ram()[..1], [0x05]);
+
+        // The 512 nibbles repeat across the whole area
+        for mirror in [0xA200, 0xA400, 0xB000, 0xBE00] {
+            assert_eq!(cartridge.read_ram(mirror), 0xF5);
+            assert_eq!(cartridge.read_ram(mirror + 0x1FF), 0xFC);
+        }
+        cartridge.write_ram(0xBFFF, 0x03);
+        assert_eq!(cartridge.read_ram(0xA1FF), 0xF3);
+
+        cartridge.write_rom(0x0000, 0x00);
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+        cartridge.write_ram(0xA000, 0x01);
+        cartridge.write_rom(0x0000, 0x0A);
+        assert_eq!(cartridge.read_ram(0xA000), 0xF5);
+    }
+
+    #[test]
+    fn mbc2_address_bit_8_selects_the_register() {
+        let mut cartridge = mbc2();
+        // Bit 8 set, a ROM bank even in the RAM enable range
+        cartridge.write_rom(0x0100, 0x05);
+        assert_eq!(cartridge.read_rom(0x4000), 0x05);
+        cartridge.write_rom(0x3EFF, 0x00);
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+        assert_eq!(cartridge.read_rom(0x4000), 0x05);
+        cartridge.write_rom(0x2000, 0x0A);
+        assert_eq!(cartridge.read_rom(0x4000), 0x05);
+        cartridge.write_ram(0xA000, 0x07);
+        assert_eq!(cartridge.read_ram(0xA000), 0xF7);
+
+        // 4 bits of bank, 0 maps to 1
+        cartridge.write_rom(0x2100, 0x1F);
+        assert_eq!(cartridge.read_rom(0x4000), 0x0F);
+        cartridge.write_rom(0x2100, 0x10);
+        assert_eq!(cartridge.read_rom(0x4000), 0x01);
+        assert_eq!(cartridge.rom_bank(0x4000), 0x01);
+    }
+
+    fn mbc7() -> Mbc7Cartridge<TestRom> {
+        let mut cartridge = Mbc7Cartridge::new(rom(0x22, 0x02, 0x00, &[]));
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x4000, 0x40);
+        cartridge
+    }
+
+    /// Drops then raises chip select, starting a new command.
+    fn eeprom_select(cartridge: &mut Mbc7Cartridge<TestRom>) {
+        cartridge.write_ram(0xA080, 0x00);
+        cartridge.write_ram(0xA080, 0x80);
+    }
+
+    /// Clocks the `count` low bits of `bits` into the EEPROM, most significant first, and
+    /// returns what DO read after each rising edge.
+    fn eeprom_clock(cartridge: &mut Mbc7Cartridge<TestRom>, bits: u32, count: u32) -> u32 {
+        let mut output = 0;
+        for index in (0..count).rev() {
+            let data_in = ((bits >> index) & 1) as u8 * 0x02;
+            cartridge.write_ram(0xA080, 0x80 | data_in);
+            cartridge.write_ram(0xA080, 0xC0 | data_in);
+            output = (output << 1) | (cartridge.read_ram(0xA080) & 0x01) as u32;
+        }
+        output
+    }
+
+    /// Start bit, 2 bit opcode and 8 bit address.
+    fn eeprom_command(cartridge: &mut Mbc7Cartridge<TestRom>, opcode: u32, address: u32) {
+        eeprom_select(cartridge);
+        eeprom_clock(cartridge, (1 << 10) | (opcode << 8) | address, 11);
+    }
+
+    fn eeprom_read(cartridge: &mut Mbc7Cartridge<TestRom>, address: u32) -> u16 {
+        eeprom_command(cartridge, 0b10, address);
+        // A dummy zero precedes the data
+        assert_eq!(cartridge.read_ram(0xA080) & 0x01, 0);
+        eeprom_clock(cartridge, 0, 16) as u16
+    }
+
+    fn eeprom_write(cartridge: &mut Mbc7Cartridge<TestRom>, address: u32, value: u16) {
+        eeprom_command(cartridge, 0b01, address);
+        eeprom_clock(cartridge, value as u32, 16);
+    }
+
+    #[test]
+    fn mbc7_eeprom_reads_and_writes_words() {
+        let mut cartridge = mbc7();
+        // Writes are ignored until EWEN
+        eeprom_write(&mut cartridge, 0x05, 0xBEEF);
+        assert_eq!(eeprom_read(&mut cartridge, 0x05), 0xFFFF);
+        assert_eq!(cartridge.rom_manager.data.saves, 0);
+
+        eeprom_command(&mut cartridge, 0b00, 0b1100_0000);
+        eeprom_command(&mut cartridge, 0b01, 0x05);
+        // Busy while the data is shifted in, programming completes with the last bit
+        assert_eq!(eeprom_clock(&mut cartridge, 0xBEEF, 16), 0x0001);
+        eeprom_select(&mut cartridge);
+        assert_eq!(cartridge.read_ram(0xA080) & 0x01, 1);
+        assert_eq!(cartridge.rom_manager.data.saves, 1);
+        assert_eq!(cartridge.save_ram()[10..12], [0xEF, 0xBE]);
+
+        eeprom_write(&mut cartridge, 0x06, 0x1234);
+        assert_eq!(eeprom_read(&mut cartridge, 0x05), 0xBEEF);
+        // Reads continue with the next word while clocked
+        assert_eq!(eeprom_clock(&mut cartridge, 0, 16), 0x1234);
+        // The top address bit is not decoded
+        assert_eq!(eeprom_read(&mut cartridge, 0x85), 0xBEEF);
+
+        // EWDS protects the contents again
+        eeprom_command(&mut cartridge, 0b00, 0b0000_0000);
+        eeprom_write(&mut cartridge, 0x05, 0x0000);
+        assert_eq!(eeprom_read(&mut cartridge, 0x05), 0xBEEF);
+
+        // Dropping chip select aborts a command
+        eeprom_command(&mut cartridge, 0b00, 0b1100_0000);
+        eeprom_command(&mut cartridge, 0b01, 0x05);
+        eeprom_clock(&mut cartridge, 0x00, 8);
+        eeprom_select(&mut cartridge);
+        assert_eq!(eeprom_read(&mut cartridge, 0x05), 0xBEEF);
+    }
+
+    #[test]
+    fn mbc7_eeprom_erases_and_writes_everything() {
+        let mut cartridge = mbc7();
+        eeprom_command(&mut cartridge, 0b00, 0b1100_0000);
+        // WRAL
+        eeprom_command(&mut cartridge, 0b00, 0b0100_0000);
+        eeprom_clock(&mut cartridge, 0x5AA5, 16);
+        assert!(cartridge
+            .save_ram()
+            .chunks(2)
+            .all(|word| word == [0xA5, 0x5A]));
+
+        // ERASE sets a single word
+        eeprom_command(&mut cartridge, 0b11, 0x10);
+        assert_eq!(eeprom_read(&mut cartridge, 0x10), 0xFFFF);
+        assert_eq!(eeprom_read(&mut cartridge, 0x11), 0x5AA5);
+
+        // ERAL
+        eeprom_command(&mut cartridge, 0b00, 0b1000_0000);
+        assert!(cartridge.save_ram().iter().all(|byte| *byte == 0xFF));
+    }
+
+    #[test]
+    fn mbc7_latches_the_accelerometer() {
+        let mut cartridge = mbc7();
+        cartridge.set_tilt(0.5, -1.0);
+        let axes = |cartridge: &Mbc7Cartridge<TestRom>| {
+            let read = |address| cartridge.read_ram(address) as u16;
+            (
+                read(0xA020) | (read(0xA030) << 8),
+                read(0xA040) | (read(0xA050) << 8),
+            )
+        };
+        // 0xAA alone does not latch
+        cartridge.write_ram(0xA010, 0xAA);
+        assert_eq!(axes(&cartridge), (0x8000, 0x8000));
+
+        cartridge.write_ram(0xA000, 0x55);
+        assert_eq!(axes(&cartridge), (0x8000, 0x8000));
+        cartridge.write_ram(0xA010, 0xAA);
+        assert_eq!(axes(&cartridge), (0x81D0 + 0x38, 0x81D0 - 0x70));
+
+        // The latched value stays until the next latch
+        cartridge.set_tilt(0.0, 0.0);
+        assert_eq!(axes(&cartridge), (0x81D0 + 0x38, 0x81D0 - 0x70));
+        cartridge.write_ram(0xA000, 0x55);
+        cartridge.write_ram(0xA010, 0xAA);
+        assert_eq!(axes(&cartridge), (0x81D0, 0x81D0));
+
+        cartridge.write_rom(0x4000, 0x00);
+        assert_eq!(cartridge.read_ram(0xA020), 0xFF);
+    }
+
+    fn huc3_command(cartridge: &mut HuC3Cartridge<TestRom>, command: u8) -> u8 {
+        cartridge.write_rom(0x0000, 0x0B);
+        cartridge.write_ram(0xA000, command);
+        cartridge.write_rom(0x0000, 0x0D);
+        assert_eq!(cartridge.read_ram(0xA000), 0x01);
+        cartridge.write_rom(0x0000, 0x0C);
+        cartridge.read_ram(0xA000)
+    }
+
+    fn huc3_seek(cartridge: &mut HuC3Cartridge<TestRom>, index: u8) {
+        huc3_command(cartridge, 0x40 | (index & 0x0F));
+        huc3_command(cartridge, 0x50 | (index >> 4));
+    }
+
+    /// Minutes and days as the game reads them, one nibble at a time.
+    fn huc3_clock(cartridge: &mut HuC3Cartridge<TestRom>) -> (u16, u16) {
+        huc3_seek(cartridge, 0x00);
+        let nibbles: Vec<u16> = (0..6)
+            .map(|_| huc3_command(cartridge, 0x10) as u16)
+            .collect();
+        (
+            nibbles[0] | (nibbles[1] << 4) | (nibbles[2] << 8),
+            nibbles[3] | (nibbles[4] << 4) | (nibbles[5] << 8),
+        )
+    }
+
+    fn huc3_set_clock(cartridge: &mut HuC3Cartridge<TestRom>, minutes: u16, days: u16) {
+        huc3_seek(cartridge, 0x00);
+        let value = (minutes as u32) | ((days as u32) << 12);
+        for nibble in 0..6 {
+            huc3_command(cartridge, 0x30 | ((value >> (nibble * 4)) & 0x0F) as u8);
+        }
+    }
+
+    #[test]
+    fn huc3_clock_is_read_and_written_through_commands() {
+        let mut cartridge = HuC3Cartridge::new(rom(0xFE, 0x01, 0x03, &[]));
+        assert_eq!(huc3_clock(&mut cartridge), (0, 0));
+        huc3_set_clock(&mut cartridge, 754, 0x123);
+        assert_eq!(huc3_clock(&mut cartridge), (754, 0x123));
+
+        // 0x2 writes without moving to the next nibble
+        huc3_seek(&mut cartridge, 0x00);
+        huc3_command(&mut cartridge, 0x25);
+        huc3_command(&mut cartridge, 0x31);
+        assert_eq!(huc3_clock(&mut cartridge), (0x2F1, 0x123));
+
+        // Flag 2 makes reads report the RTC as ready
+        huc3_command(&mut cartridge, 0x62);
+        assert_eq!(huc3_command(&mut cartridge, 0x10), 0x01);
+        huc3_command(&mut cartridge, 0x60);
+
+        // RAM is back once the mode is
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+        cartridge.write_rom(0x0000, 0x00);
+        assert_eq!(cartridge.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn huc3_clock_follows_the_host() {
+        let mut cartridge = HuC3Cartridge::new(rom(0xFE, 0x01, 0x03, &[]));
+        huc3_set_clock(&mut cartridge, 1438, 7);
+        cartridge.rom_manager.data.clock += 150 * 1_000_000;
+        assert_eq!(huc3_clock(&mut cartridge), (0, 8));
+        // The 30 seconds left over are kept
+        cartridge.rom_manager.data.clock += 30 * 1_000_000;
+        assert_eq!(huc3_clock(&mut cartridge), (1, 8));
+
+        huc3_set_clock(&mut cartridge, 1439, 0xFFF);
+        cartridge.rom_manager.data.clock += 60 * 1_000_000;
+        assert_eq!(huc3_clock(&mut cartridge), (0, 0));
+    }
+
+    fn encoded_state(cartridge: &dyn Cartridge) -> Vec<u8> {
+        let mut data = Vec::new();
+        crate::savestate::binary::to_bytes(&cartridge.create_state(), &mut data).unwrap();
+        data
+    }
+
+    /// Loads the state of `original` into `fresh`, which must then snapshot the same.
+    fn restore(original: &dyn Cartridge, fresh: &mut dyn Cartridge) {
+        assert_ne!(encoded_state(original), encoded_state(fresh));
+        fresh.load_state(original.create_state());
+        assert_eq!(encoded_state(original), encoded_state(fresh));
+    }
+
+    #[test]
+    fn mbc1_state_round_trip() {
+        let mut cartridge = mbc1(0x05);
+        cartridge.write_rom(0x2000, 0x05);
+        cartridge.write_rom(0x4000, 0x01);
+        cartridge.write_rom(0x6000, 0x01);
+        cartridge.write_ram(0xA000, 0x42);
+
+        let mut restored = Mbc1Cartridge::new(rom(0x03, 0x05, 0x03, &[]).data, 4, 64, true);
+        restore(&cartridge, &mut restored);
+        assert_eq!(mbc1_banks(&restored), (0x20, 0x25));
+        assert_eq!(restored.read_ram(0xA000), 0x42);
+        restored.write_rom(0x4000, 0x00);
+        assert_eq!(restored.read_ram(0xA000), 0x00);
+    }
+
+    #[test]
+    fn mbc2_state_round_trip() {
+        let mut cartridge = mbc2();
+        cartridge.write_rom(0x2100, 0x03);
+        cartridge.write_ram(0xA010, 0x07);
+
+        let mut restored = Mbc2Cartridge::new(rom(0x06, 0x03, 0x00, &[]));
+        restore(&cartridge, &mut restored);
+        assert_eq!(restored.read_rom(0x4000), 0x03);
+        assert_eq!(restored.read_ram(0xA010), 0xF7);
+    }
+
+    #[test]
+    fn mbc5_state_round_trip() {
+        let mut cartridge = Mbc5Cartridge::new(rom(0x1E, 0x05, 0x03, &[]));
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x2000, 0x13);
+        cartridge.write_rom(0x4000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+
+        let mut restored = Mbc5Cartridge::new(rom(0x1E, 0x05, 0x03, &[]));
+        restore(&cartridge, &mut restored);
+        assert_eq!(restored.read_rom(0x4000), 0x13);
+        assert_eq!(restored.read_ram(0xA000), 0x42);
+        assert!(restored.rumble());
+    }
+
+    #[test]
+    fn mbc7_state_round_trip() {
+        let mut cartridge = mbc7();
+        eeprom_command(&mut cartridge, 0b00, 0xC0);
+        eeprom_write(&mut cartridge, 0x12, 0xCAFE);
+        cartridge.set_tilt(0.5, -1.0);
+        cartridge.write_ram(0xA000, 0x55);
+        cartridge.write_ram(0xA010, 0xAA);
+
+        let mut restored = Mbc7Cartridge::new(rom(0x22, 0x02, 0x00, &[]));
+        restore(&cartridge, &mut restored);
+        assert_eq!(restored.read_ram(0xA020), 0x08);
+        assert_eq!(restored.read_ram(0xA030), 0x82);
+        assert_eq!(eeprom_read(&mut restored, 0x12), 0xCAFE);
+    }
+
+    #[test]
+    fn huc1_state_round_trip() {
+        let mut cartridge = HuC1Cartridge::new(rom(0xFF, 0x02, 0x03, &[]));
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x2000, 0x06);
+        cartridge.write_rom(0x4000, 0x02);
+        cartridge.write_ram(0xA000, 0x42);
+
+        let mut restored = HuC1Cartridge::new(rom(0xFF, 0x02, 0x03, &[]));
+        restore(&cartridge, &mut restored);
+        assert_eq!(restored.read_rom(0x4000), 0x06);
+        assert_eq!(restored.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn huc3_state_round_trip() {
+        let mut cartridge = HuC3Cartridge::new(rom(0xFE, 0x01, 0x03, &[]));
+        huc3_set_clock(&mut cartridge, 754, 0x123);
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x2000, 0x02);
+        cartridge.write_ram(0xA000, 0x42);
+
+        let mut restored = HuC3Cartridge::new(rom(0xFE, 0x01, 0x03, &[]));
+        restore(&cartridge, &mut restored);
+        assert_eq!(restored.read_rom(0x4000), 0x02);
+        assert_eq!(restored.read_ram(0xA000), 0x42);
+        restored.rom_manager.data.clock += 60 * 1_000_000;
+        assert_eq!(huc3_clock(&mut restored), (755, 0x123));
+    }
+
+    #[test]
+    fn camera_state_round_trip() {
+        let mut cartridge = camera();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x2000, 0x01);
+        cartridge.write_rom(0x4000, 0x03);
+        cartridge.write_ram(0xA100, 0x5A);
+
+        let mut restored = PocketCameraCartridge::new(rom(0xFC, 0x00, 0x04, &[]));
+        restore(&cartridge, &mut restored);
+        assert_eq!(restored.read_rom(0x4000), 0x01);
+        assert_eq!(restored.read_ram(0xA100), 0x5A);
+        restored.write_rom(0x4000, 0x10);
+        assert_eq!(restored.read_ram(0xA001), 0x00);
+    }
+
+    #[test]
+    fn states_of_another_mapper_are_ignored() {
+        let mut cartridge = mbc2();
+        let before = encoded_state(&cartridge);
+        cartridge.load_state(mbc1(0x01).create_state());
+        assert_eq!(encoded_state(&cartridge), before);
+    }
+}
@@ -0,0 +1,332 @@
+//! Game Genie and GameShark codes.
+//!
+//! Game Genie codes replace bytes read from the cartridge ROM, optionally only when the
+//! original byte matches a compare value so the code survives bank switching. GameShark
+//! codes write a byte to RAM once per frame when the PPU enters VBlank.
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatError {
+    /// The code has neither the Game Genie nor the GameShark length.
+    InvalidLength,
+    /// The code contains something other than hexadecimal digits and dashes.
+    InvalidDigit,
+    /// The code targets memory its format cannot patch.
+    InvalidAddress,
+}
+
+impl core::fmt::Display for CheatError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CheatError::InvalidLength => write!(f, "cheat code has an invalid length"),
+            CheatError::InvalidDigit => write!(f, "cheat code contains an invalid digit"),
+            CheatError::InvalidAddress => write!(f, "cheat code targets an invalid address"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CheatError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameGenieCode {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+impl GameGenieCode {
+    /// Decodes `ABC-DEF` or `ABC-DEF-GHI`: `AB` is the new value, `FCDE` the address with
+    /// `F` inverted and `GI` the compare byte, rotated right by two and xored with 0xBA.
+    /// `H` is not used by the hardware.
+    pub fn parse(code: &str) -> Result<Self, CheatError> {
+        let (digits, length) = hex_digits::<9>(code)?;
+        if length != 6 && length != 9 {
+            return Err(CheatError::InvalidLength);
+        }
+        let value = (digits[0] << 4) | digits[1];
+        let address = (((digits[5] ^ 0xF) as u16) << 12)
+            | ((digits[2] as u16) << 8)
+            | ((digits[3] as u16) << 4)
+            | digits[4] as u16;
+        if address >= 0x8000 {
+            return Err(CheatError::InvalidAddress);
+        }
+        let compare = if length == 9 {
+            Some(((digits[6] << 4) | digits[8]).rotate_right(2) ^ 0xBA)
+        } else {
+            None
+        };
+        Ok(Self {
+            address,
+            value,
+            compare,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameSharkCode {
+    /// External RAM bank on the original device, writes go to whatever bank is mapped.
+    pub bank: u8,
+    pub address: u16,
+    pub value: u8,
+}
+
+impl GameSharkCode {
+    /// Decodes `TTVVLLHH`: a type or bank byte, the value and the little endian address.
+    pub fn parse(code: &str) -> Result<Self, CheatError> {
+        let (digits, length) = hex_digits::<8>(code)?;
+        if length != 8 {
+            return Err(CheatError::InvalidLength);
+        }
+        let byte = |index: usize| (digits[index * 2] << 4) | digits[index * 2 + 1];
+        let address = u16::from_le_bytes([byte(2), byte(3)]);
+        if address < 0xA000 {
+            return Err(CheatError::InvalidAddress);
+        }
+        Ok(Self {
+            bank: byte(0),
+            address,
+            value: byte(1),
+        })
+    }
+}
+
+/// Collects up to `N` hexadecimal digits, dashes and spaces are skipped.
+fn hex_digits<const N: usize>(code: &str) -> Result<([u8; N], usize), CheatError> {
+    let mut digits = [0; N];
+    let mut length = 0;
+    for character in code.chars().filter(|c| *c != '-' && !c.is_whitespace()) {
+        let digit = character.to_digit(16).ok_or(CheatError::InvalidDigit)?;
+        if length == N {
+            return Err(CheatError::InvalidLength);
+        }
+        digits[length] = digit as u8;
+        length += 1;
+    }
+    Ok((digits, length))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatCode {
+    GameGenie(GameGenieCode),
+    GameShark(GameSharkCode),
+}
+
+impl CheatCode {
+    /// Detects the format from the number of digits, eight for GameShark and six or nine
+    /// for Game Genie.
+    pub fn parse(code: &str) -> Result<Self, CheatError> {
+        let length = code
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .count();
+        match length {
+            6 | 9 => GameGenieCode::parse(code).map(CheatCode::GameGenie),
+            8 => GameSharkCode::parse(code).map(CheatCode::GameShark),
+            _ => Err(CheatError::InvalidLength),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cheat {
+    /// The code as it was entered.
+    pub code: String,
+    pub kind: CheatCode,
+    pub enabled: bool,
+}
+
+pub struct CheatManager {
+    cheats: Vec<Cheat>,
+    // Lets ROM reads skip the lookup when no Game Genie code is on
+    rom_patches: bool,
+}
+
+impl CheatManager {
+    pub fn new() -> Self {
+        Self {
+            cheats: Vec::new(),
+            rom_patches: false,
+        }
+    }
+
+    /// Parses and enables a code, returning its index.
+    pub fn add(&mut self, code: &str) -> Result<usize, CheatError> {
+        let kind = CheatCode::parse(code)?;
+        self.cheats.push(Cheat {
+            code: String::from(code.trim()),
+            kind,
+            enabled: true,
+        });
+        self.update();
+        Ok(self.cheats.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        if index >= self.cheats.len() {
+            return None;
+        }
+        let cheat = self.cheats.remove(index);
+        self.update();
+        Some(cheat)
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(cheat) = self.cheats.get_mut(index) {
+            cheat.enabled = enabled;
+        }
+        self.update();
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+        self.update();
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    fn update(&mut self) {
+        self.rom_patches = self
+            .cheats
+            .iter()
+            .any(|cheat| cheat.enabled && matches!(cheat.kind, CheatCode::GameGenie(_)));
+    }
+
+    /// Applies the enabled Game Genie codes to a byte read from the cartridge ROM.
+    #[inline(always)]
+    pub fn patch_rom(&self, address: u16, value: u8) -> u8 {
+        if !self.rom_patches {
+            return value;
+        }
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .find_map(|cheat| match cheat.kind {
+                CheatCode::GameGenie(genie)
+                    if genie.address == address
+                        && genie.compare.is_none_or(|compare| compare == value) =>
+                {
+                    Some(genie.value)
+                }
+                _ => None,
+            })
+            .unwrap_or(value)
+    }
+
+    /// Enabled GameShark writes, applied once per frame.
+    pub fn ram_writes(&self) -> impl Iterator<Item = GameSharkCode> + '_ {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .filter_map(|cheat| match cheat.kind {
+                CheatCode::GameShark(shark) => Some(shark),
+                _ => None,
+            })
+    }
+}
+
+impl Default for CheatManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::gameboy;
+
+    #[test]
+    fn parses_game_genie_codes() {
+        assert_eq!(
+            CheatCode::parse("00A-17B-C49"),
+            Ok(CheatCode::GameGenie(GameGenieCode {
+                address: 0x4A17,
+                value: 0x00,
+                compare: Some(0xC8),
+            }))
+        );
+        assert_eq!(
+            CheatCode::parse("3ea-d2f"),
+            Ok(CheatCode::GameGenie(GameGenieCode {
+                address: 0x0AD2,
+                value: 0x3E,
+                compare: None,
+            }))
+        );
+        // The inverted high nibble would point at VRAM
+        assert_eq!(
+            GameGenieCode::parse("00A-177"),
+            Err(CheatError::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn parses_gameshark_codes() {
+        // Sets the first byte of the money in Pokémon Red to 99
+        assert_eq!(
+            CheatCode::parse("019947D3"),
+            Ok(CheatCode::GameShark(GameSharkCode {
+                bank: 0x01,
+                address: 0xD347,
+                value: 0x99,
+            }))
+        );
+        assert_eq!(
+            GameSharkCode::parse("01FF0080"),
+            Err(CheatError::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        assert_eq!(CheatCode::parse(""), Err(CheatError::InvalidLength));
+        assert_eq!(CheatCode::parse("00A-17"), Err(CheatError::InvalidLength));
+        assert_eq!(
+            CheatCode::parse("0199-47D3-00"),
+            Err(CheatError::InvalidLength)
+        );
+        assert_eq!(
+            CheatCode::parse("00A-17B-C4G"),
+            Err(CheatError::InvalidDigit)
+        );
+        assert_eq!(CheatCode::parse("0199é7D3"), Err(CheatError::InvalidDigit));
+    }
+
+    #[test]
+    fn patch_rom_checks_the_compare_byte() {
+        let mut cheats = CheatManager::new();
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0xC8);
+        cheats.add("00A-17B-C49").unwrap();
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0x00);
+        // Another bank is mapped, the original byte differs
+        assert_eq!(cheats.patch_rom(0x4A17, 0x3D), 0x3D);
+        assert_eq!(cheats.patch_rom(0x4A18, 0xC8), 0xC8);
+
+        let index = cheats.add("3EA-D2F").unwrap();
+        assert_eq!(cheats.patch_rom(0x0AD2, 0x12), 0x3E);
+        cheats.set_enabled(index, false);
+        assert_eq!(cheats.patch_rom(0x0AD2, 0x12), 0x12);
+        cheats.remove(0);
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0xC8);
+    }
+
+    #[test]
+    fn gameshark_writes_land_at_vblank() {
+        // JR -2
+        let mut gameboy = gameboy(&[0x18, 0xFE]);
+        gameboy.cheats().add("019947D3").unwrap();
+        assert_eq!(gameboy.peek(0xD347), 0x00);
+        while gameboy.cpu.interface.gpu.scanline != 144 {
+            gameboy.tick();
+        }
+        assert_eq!(gameboy.peek(0xD347), 0x99);
+        assert_eq!(gameboy.cheats().ram_writes().count(), 1);
+    }
+}
@@ -0,0 +1,489 @@
+use arrayvec::ArrayVec;
+
+use num_traits::FromPrimitive;
+
+use super::rom::{Region, RomManager, RomSize, NINTENDO_LOGO, NINTENDO_LOGO_OFFSET};
+
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+
+const TITLE_START: usize = 0x134;
+const MANUFACTURER_START: usize = 0x13F;
+const CGB_FLAG: usize = 0x143;
+const NEW_LICENSEE_START: usize = 0x144;
+const SGB_FLAG: usize = 0x146;
+const CARTRIDGE_TYPE: usize = 0x147;
+const ROM_SIZE: usize = 0x148;
+const RAM_SIZE: usize = 0x149;
+const DESTINATION: usize = 0x14A;
+const OLD_LICENSEE: usize = 0x14B;
+const VERSION: usize = 0x14C;
+const HEADER_CHECKSUM: usize = 0x14D;
+const GLOBAL_CHECKSUM: usize = 0x14E;
+
+/// Old licensee value telling that the publisher is in the new licensee field.
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CgbSupport {
+    /// Monochrome only game
+    None,
+    /// Runs on both DMG and CGB with color enhancements
+    Enhanced,
+    /// Refuses to run on a DMG
+    Only,
+}
+
+impl CgbSupport {
+    pub fn from_value(value: u8) -> CgbSupport {
+        match value {
+            0xC0 => CgbSupport::Only,
+            0x80 => CgbSupport::Enhanced,
+            _ => CgbSupport::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Licensee {
+    Old(u8),
+    New([u8; 2]),
+}
+
+impl Licensee {
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Licensee::Old(code) => old_licensee_name(*code),
+            Licensee::New(code) => new_licensee_name(code),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HeaderMismatch {
+    /// The Nintendo logo is not the one the boot ROM checks against
+    Logo,
+    HeaderChecksum {
+        stored: u8,
+        computed: u8,
+    },
+    GlobalChecksum {
+        stored: u16,
+        computed: u16,
+    },
+    /// The file length differs from the ROM size code
+    RomSize {
+        declared: usize,
+        actual: usize,
+    },
+}
+
+/// Every field of the cartridge header located at 0x100-0x14F.
+#[derive(Clone)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub manufacturer_code: Option<[u8; 4]>,
+    pub cgb_support: CgbSupport,
+    pub new_licensee_code: [u8; 2],
+    pub sgb_support: bool,
+    pub cartridge_type: u8,
+    pub rom_size: u8,
+    pub ram_size: u8,
+    pub destination: u8,
+    pub old_licensee_code: u8,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    pub logo_valid: bool,
+}
+
+impl CartridgeHeader {
+    /// Decodes the header, `data` must hold at least 0x150 bytes.
+    pub fn from_bytes<RM: RomManager>(data: &RM) -> CartridgeHeader {
+        let cgb_support = CgbSupport::from_value(data[CGB_FLAG]);
+        // CGB era titles give up their last bytes to the manufacturer code and CGB flag
+        let (title_end, manufacturer_code) = if cgb_support == CgbSupport::None {
+            (CGB_FLAG + 1, None)
+        } else {
+            let mut code = [0; 4];
+            code.copy_from_slice(&data[MANUFACTURER_START..CGB_FLAG]);
+            let valid = code.iter().all(|c| c.is_ascii_uppercase());
+            (
+                if valid { MANUFACTURER_START } else { CGB_FLAG },
+                if valid { Some(code) } else { None },
+            )
+        };
+        let title = String::from_utf8_lossy(&data[TITLE_START..title_end]);
+        let old_licensee_code = data[OLD_LICENSEE];
+
+        CartridgeHeader {
+            title: String::from(title.trim_end_matches('\0')),
+            manufacturer_code,
+            cgb_support,
+            new_licensee_code: [data[NEW_LICENSEE_START], data[NEW_LICENSEE_START + 1]],
+            sgb_support: data[SGB_FLAG] == 0x03 && old_licensee_code == USE_NEW_LICENSEE,
+            cartridge_type: data[CARTRIDGE_TYPE],
+            rom_size: data[ROM_SIZE],
+            ram_size: data[RAM_SIZE],
+            destination: data[DESTINATION],
+            old_licensee_code,
+            version: data[VERSION],
+            header_checksum: data[HEADER_CHECKSUM],
+            global_checksum: u16::from_be_bytes([data[GLOBAL_CHECKSUM], data[GLOBAL_CHECKSUM + 1]]),
+            logo_valid: data[NINTENDO_LOGO_OFFSET..NINTENDO_LOGO_OFFSET + NINTENDO_LOGO.len()]
+                == NINTENDO_LOGO,
+        }
+    }
+
+    pub fn licensee(&self) -> Licensee {
+        if self.old_licensee_code == USE_NEW_LICENSEE {
+            Licensee::New(self.new_licensee_code)
+        } else {
+            Licensee::Old(self.old_licensee_code)
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        Region::from_value(self.destination)
+    }
+
+    /// Checksum over 0x134-0x14C, the boot ROM locks up when it does not match.
+    pub fn compute_header_checksum<RM: RomManager>(data: &RM) -> u8 {
+        (TITLE_START..HEADER_CHECKSUM).fold(0u8, |checksum, index| {
+            checksum.wrapping_sub(data[index]).wrapping_sub(1)
+        })
+    }
+
+    /// Sum of every byte of the ROM except the global checksum itself.
+    pub fn compute_global_checksum<RM: RomManager>(data: &RM) -> u16 {
+        (0..data.size())
+            .filter(|index| *index != GLOBAL_CHECKSUM && *index != GLOBAL_CHECKSUM + 1)
+            .fold(0u16, |checksum, index| {
+                checksum.wrapping_add(data[index] as u16)
+            })
+    }
+
+    /// Reports every field that does not match the ROM contents, an empty list means a
+    /// clean dump.
+    pub fn mismatches<RM: RomManager>(&self, data: &RM) -> ArrayVec<HeaderMismatch, 4> {
+        let mut mismatches = ArrayVec::new();
+        if !self.logo_valid {
+            mismatches.push(HeaderMismatch::Logo);
+        }
+        let computed = Self::compute_header_checksum(data);
+        if computed != self.header_checksum {
+            mismatches.push(HeaderMismatch::HeaderChecksum {
+                stored: self.header_checksum,
+                computed,
+            });
+        }
+        let computed = Self::compute_global_checksum(data);
+        if computed != self.global_checksum {
+            mismatches.push(HeaderMismatch::GlobalChecksum {
+                stored: self.global_checksum,
+                computed,
+            });
+        }
+        if let Some(rom_size) = RomSize::from_u8(self.rom_size) {
+            let declared = rom_size.expected_size() as usize;
+            if declared != data.size() {
+                mismatches.push(HeaderMismatch::RomSize {
+                    declared,
+                    actual: data.size(),
+                });
+            }
+        }
+        mismatches
+    }
+}
+
+fn new_licensee_name(code: &[u8; 2]) -> Option<&'static str> {
+    let name = match code {
+        b"00" => "None",
+        b"01" => "Nintendo R&D1",
+        b"08" => "Capcom",
+        b"13" => "Electronic Arts",
+        b"18" => "Hudson Soft",
+        b"19" => "b-ai",
+        b"20" => "kss",
+        b"22" => "pow",
+        b"24" => "PCM Complete",
+        b"25" => "san-x",
+        b"28" => "Kemco Japan",
+        b"29" => "seta",
+        b"30" => "Viacom",
+        b"31" => "Nintendo",
+        b"32" => "Bandai",
+        b"33" => "Ocean/Acclaim",
+        b"34" => "Konami",
+        b"35" => "Hector",
+        b"37" => "Taito",
+        b"38" => "Hudson",
+        b"39" => "Banpresto",
+        b"41" => "Ubi Soft",
+        b"42" => "Atlus",
+        b"44" => "Malibu",
+        b"46" => "angel",
+        b"47" => "Bullet-Proof",
+        b"49" => "irem",
+        b"50" => "Absolute",
+        b"51" => "Acclaim",
+        b"52" => "Activision",
+        b"53" => "American sammy",
+        b"54" => "Konami",
+        b"55" => "Hi tech entertainment",
+        b"56" => "LJN",
+        b"57" => "Matchbox",
+        b"58" => "Mattel",
+        b"59" => "Milton Bradley",
+        b"60" => "Titus",
+        b"61" => "Virgin",
+        b"64" => "LucasArts",
+        b"67" => "Ocean",
+        b"69" => "Electronic Arts",
+        b"70" => "Infogrames",
+        b"71" => "Interplay",
+        b"72" => "Broderbund",
+        b"73" => "sculptured",
+        b"75" => "sci",
+        b"78" => "THQ",
+        b"79" => "Accolade",
+        b"80" => "misawa",
+        b"83" => "lozc",
+        b"86" => "Tokuma Shoten Intermedia",
+        b"87" => "Tsukuda Original",
+        b"91" => "Chunsoft",
+        b"92" => "Video system",
+        b"93" => "Ocean/Acclaim",
+        b"95" => "Varie",
+        b"96" => "Yonezawa/s'pal",
+        b"97" => "Kaneko",
+        b"99" => "Pack in soft",
+        b"A4" => "Konami (Yu-Gi-Oh!)",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn old_licensee_name(code: u8) -> Option<&'static str> {
+    let name = match code {
+        0x00 => "None",
+        0x01 => "Nintendo",
+        0x08 => "Capcom",
+        0x09 => "Hot-B",
+        0x0A => "Jaleco",
+        0x0B => "Coconuts Japan",
+        0x0C => "Elite Systems",
+        0x13 => "Electronic Arts",
+        0x18 => "Hudsonsoft",
+        0x19 => "ITC Entertainment",
+        0x1A => "Yanoman",
+        0x1D => "Japan Clary",
+        0x1F => "Virgin Interactive",
+        0x24 => "PCM Complete",
+        0x25 => "San-X",
+        0x28 => "Kotobuki Systems",
+        0x29 => "Seta",
+        0x30 => "Infogrames",
+        0x31 => "Nintendo",
+        0x32 => "Bandai",
+        0x34 => "Konami",
+        0x35 => "HectorSoft",
+        0x38 => "Capcom",
+        0x39 => "Banpresto",
+        0x3C => "Entertainment i",
+        0x3E => "Gremlin",
+        0x41 => "Ubisoft",
+        0x42 => "Atlus",
+        0x44 => "Malibu",
+        0x46 => "Angel",
+        0x47 => "Spectrum Holoby",
+        0x49 => "Irem",
+        0x4A => "Virgin Interactive",
+        0x4D => "Malibu",
+        0x4F => "U.S. Gold",
+        0x50 => "Absolute",
+        0x51 => "Acclaim",
+        0x52 => "Activision",
+        0x53 => "American Sammy",
+        0x54 => "GameTek",
+        0x55 => "Park Place",
+        0x56 => "LJN",
+        0x57 => "Matchbox",
+        0x59 => "Milton Bradley",
+        0x5A => "Mindscape",
+        0x5B => "Romstar",
+        0x5C => "Naxat Soft",
+        0x5D => "Tradewest",
+        0x60 => "Titus",
+        0x61 => "Virgin Interactive",
+        0x67 => "Ocean Interactive",
+        0x69 => "Electronic Arts",
+        0x6E => "Elite Systems",
+        0x6F => "Electro Brain",
+        0x70 => "Infogrames",
+        0x71 => "Interplay",
+        0x72 => "Broderbund",
+        0x73 => "Sculptered Soft",
+        0x75 => "The Sales Curve",
+        0x78 => "t.hq",
+        0x79 => "Accolade",
+        0x7A => "Triffix Entertainment",
+        0x7C => "Microprose",
+        0x7F => "Kemco",
+        0x80 => "Misawa Entertainment",
+        0x83 => "Lozc",
+        0x86 => "Tokuma Shoten Intermedia",
+        0x8B => "Bullet-Proof Software",
+        0x8C => "Vic Tokai",
+        0x8E => "Ape",
+        0x8F => "I'Max",
+        0x91 => "Chunsoft",
+        0x92 => "Video System",
+        0x93 => "Tsubaraya Productions",
+        0x95 => "Varie",
+        0x96 => "Yonezawa/S'Pal",
+        0x97 => "Kaneko",
+        0x99 => "Arc",
+        0x9A => "Nihon Bussan",
+        0x9B => "Tecmo",
+        0x9C => "Imagineer",
+        0x9D => "Banpresto",
+        0x9F => "Nova",
+        0xA1 => "Hori Electric",
+        0xA2 => "Bandai",
+        0xA4 => "Konami",
+        0xA6 => "Kawada",
+        0xA7 => "Takara",
+        0xA9 => "Technos Japan",
+        0xAA => "Broderbund",
+        0xAC => "Toei Animation",
+        0xAD => "Toho",
+        0xAF => "Namco",
+        0xB0 => "Acclaim",
+        0xB1 => "ASCII or Nexsoft",
+        0xB2 => "Bandai",
+        0xB4 => "Square Enix",
+        0xB6 => "HAL Laboratory",
+        0xB7 => "SNK",
+        0xB9 => "Pony Canyon",
+        0xBA => "Culture Brain",
+        0xBB => "Sunsoft",
+        0xBD => "Sony Imagesoft",
+        0xBF => "Sammy",
+        0xC0 => "Taito",
+        0xC2 => "Kemco",
+        0xC3 => "Squaresoft",
+        0xC4 => "Tokuma Shoten Intermedia",
+        0xC5 => "Data East",
+        0xC6 => "Tonkinhouse",
+        0xC8 => "Koei",
+        0xC9 => "UFL",
+        0xCA => "Ultra",
+        0xCB => "Vap",
+        0xCC => "Use Corporation",
+        0xCD => "Meldac",
+        0xCE => "Pony Canyon",
+        0xCF => "Angel",
+        0xD0 => "Taito",
+        0xD1 => "Sofel",
+        0xD2 => "Quest",
+        0xD3 => "Sigma Enterprises",
+        0xD4 => "ASK Kodansha",
+        0xD6 => "Naxat Soft",
+        0xD7 => "Copya System",
+        0xD9 => "Banpresto",
+        0xDA => "Tomy",
+        0xDB => "LJN",
+        0xDD => "NCS",
+        0xDE => "Human",
+        0xDF => "Altron",
+        0xE0 => "Jaleco",
+        0xE1 => "Towa Chiki",
+        0xE2 => "Yutaka",
+        0xE3 => "Varie",
+        0xE5 => "Epoch",
+        0xE7 => "Athena",
+        0xE8 => "Asmik ACE Entertainment",
+        0xE9 => "Natsume",
+        0xEA => "King Records",
+        0xEB => "Atlus",
+        0xEC => "Epic/Sony Records",
+        0xEE => "IGS",
+        0xF0 => "A Wave",
+        0xF3 => "Extreme Entertainment",
+        0xFF => "LJN",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{rom_data, TestRom};
+
+    /// A ROM with the logo and both checksums filled in like a clean dump.
+    fn clean() -> TestRom {
+        let mut data = rom_data(0x00, 0x00, 0x00, &[]);
+        data.data[NINTENDO_LOGO_OFFSET..NINTENDO_LOGO_OFFSET + NINTENDO_LOGO.len()]
+            .copy_from_slice(&NINTENDO_LOGO);
+        data.data[HEADER_CHECKSUM] = CartridgeHeader::compute_header_checksum(&data);
+        let global = CartridgeHeader::compute_global_checksum(&data);
+        data.data[GLOBAL_CHECKSUM..GLOBAL_CHECKSUM + 2].copy_from_slice(&global.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn clean_header_has_no_mismatch() {
+        let data = clean();
+        let header = CartridgeHeader::from_bytes(&data);
+        assert!(header.logo_valid);
+        assert_eq!(header.title, "TEST");
+        assert!(header.mismatches(&data).is_empty());
+    }
+
+    #[test]
+    fn reports_corrupted_checksums() {
+        let mut data = clean();
+        let header = CartridgeHeader::from_bytes(&data);
+        // 'T' becoming 'U' in the title breaks both checksums
+        data.data[TITLE_START] += 1;
+        assert_eq!(
+            header.mismatches(&data).as_slice(),
+            &[
+                HeaderMismatch::HeaderChecksum {
+                    stored: header.header_checksum,
+                    computed: header.header_checksum.wrapping_sub(1),
+                },
+                HeaderMismatch::GlobalChecksum {
+                    stored: header.global_checksum,
+                    computed: header.global_checksum.wrapping_add(1),
+                },
+            ]
+        );
+
+        // Outside of the header only the global checksum covers it
+        let mut data = clean();
+        data.data[0x4000] = 0x55;
+        let header = CartridgeHeader::from_bytes(&data);
+        assert_eq!(
+            header.mismatches(&data).as_slice(),
+            &[HeaderMismatch::GlobalChecksum {
+                stored: header.global_checksum,
+                computed: header.global_checksum.wrapping_add(0x54),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_a_wrong_logo() {
+        let mut data = clean();
+        data.data[NINTENDO_LOGO_OFFSET] = 0x00;
+        let global = CartridgeHeader::compute_global_checksum(&data);
+        data.data[GLOBAL_CHECKSUM..GLOBAL_CHECKSUM + 2].copy_from_slice(&global.to_be_bytes());
+        let header = CartridgeHeader::from_bytes(&data);
+        assert!(!header.logo_valid);
+        assert_eq!(header.mismatches(&data).as_slice(), &[HeaderMismatch::Logo]);
+    }
+}
@@ -0,0 +1,58 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+/// Infrared transceiver found on HuC1/HuC3 cartridges.
+pub trait InfraredPort {
+    /// Turns the cartridge LED on or off.
+    fn set_led(&mut self, on: bool);
+    /// Whether the sensor is currently receiving light.
+    fn light_detected(&self) -> bool;
+}
+
+/// Port with nothing in front of it, the LED goes nowhere and no light is ever received.
+pub struct NullInfrared;
+
+impl InfraredPort for NullInfrared {
+    fn set_led(&mut self, _on: bool) {}
+
+    fn light_detected(&self) -> bool {
+        false
+    }
+}
+
+/// One end of a point to point infrared link, see [`infrared_link`].
+pub struct InfraredLink {
+    led: Arc<AtomicBool>,
+    remote_led: Arc<AtomicBool>,
+}
+
+/// Creates two connected ports, the LED of each end is seen by the sensor of the other,
+/// which allows two emulator instances to talk to each other.
+pub fn infrared_link() -> (InfraredLink, InfraredLink) {
+    let first = Arc::new(AtomicBool::new(false));
+    let second = Arc::new(AtomicBool::new(false));
+    (
+        InfraredLink {
+            led: first.clone(),
+            remote_led: second.clone(),
+        },
+        InfraredLink {
+            led: second,
+            remote_led: first,
+        },
+    )
+}
+
+impl InfraredPort for InfraredLink {
+    fn set_led(&mut self, on: bool) {
+        self.led.store(on, Ordering::Relaxed);
+    }
+
+    fn light_detected(&self) -> bool {
+        self.remote_led.load(Ordering::Relaxed)
+    }
+}
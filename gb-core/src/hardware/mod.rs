@@ -1,22 +1,30 @@
 use crate::cpu::Interface;
+use crate::debugger::Debugger;
 use crate::hardware::boot_rom::Bootrom;
 use crate::hardware::cartridge::Cartridge;
+use crate::hardware::cheats::CheatManager;
 use crate::hardware::color_palette::Color;
 use crate::hardware::input::InputController;
 use crate::hardware::interrupt_handler::{InterruptHandler, InterruptLine};
+use crate::hardware::observer::{notify_read, notify_write, BusObserver};
 use crate::hardware::ppu::Ppu;
 use crate::hardware::timer::Timer;
 use crate::hardware::work_ram::WorkRam;
 use crate::memory::Memory;
 
 use ppu::{Control, PPuState};
-use sound::Sound;
+use sound::{Sound, SoundState};
 
 pub mod boot_rom;
+pub mod camera;
 pub mod cartridge;
+pub mod cheats;
 pub mod color_palette;
+pub mod header;
+pub mod infrared;
 pub mod input;
 pub mod interrupt_handler;
+pub mod observer;
 pub mod ppu;
 pub mod rom;
 pub mod sound;
@@ -58,6 +66,13 @@ pub struct Hardware<'a, T: Screen> {
     dma: Dma,
     pub sound: Sound,
     pub input_controller: InputController,
+    pub cheats: CheatManager,
+    pub debugger: Debugger,
+    pub(crate) observer: Option<Box<dyn BusObserver + 'a>>,
+    /// Cycles emulated since power on.
+    pub cycles: u64,
+    // Reads of LY return 0x90 like in the gameboy-doctor reference logs
+    pub(crate) doctor_ly: bool,
 }
 
 impl<'a, T: Screen> Hardware<'a, T> {
@@ -88,6 +103,47 @@ impl<'a, T: Screen> Hardware<'a, T> {
             dma: Dma { source: 0 },
             sound: Sound::new_dmg(player),
             input_controller: InputController::new(),
+            cheats: CheatManager::new(),
+            debugger: Debugger::new(),
+            observer: None,
+            cycles: 0,
+            doctor_ly: false,
+        }
+    }
+
+    /// Writes the enabled GameShark codes, called when the PPU enters VBlank. The writes
+    /// come from outside the CPU so watchpoints and observers do not see them.
+    pub fn apply_cheats(&mut self) {
+        let cheats = core::mem::take(&mut self.cheats);
+        for code in cheats.ram_writes() {
+            self.write_byte(code.address, code.value);
+        }
+        self.cheats = cheats;
+    }
+
+    #[inline(always)]
+    fn observed(&self) -> bool {
+        self.debugger.watching() || self.observer.is_some()
+    }
+
+    /// Reports a bus access to the debugger watchpoints, then to the attached observer.
+    fn notify(&mut self, address: u16, value: u8, write: bool) {
+        let notify = if write { notify_write } else { notify_read };
+        let cycle = self.cycles;
+        notify(&mut self.debugger, address, value, cycle);
+        if let Some(observer) = self.observer.as_mut() {
+            notify(observer.as_mut(), address, value, cycle);
+        }
+    }
+
+    /// Reports the instruction about to run from `address` to the observer.
+    pub(crate) fn observe_execute(&mut self, address: u16) {
+        if self.observer.is_none() {
+            return;
+        }
+        let opcode = self.peek_byte(address);
+        if let Some(observer) = self.observer.as_mut() {
+            observer.execute(address, opcode, self.cycles);
         }
     }
 
@@ -98,9 +154,23 @@ impl<'a, T: Screen> Hardware<'a, T> {
             hiram: self.hiram,
             timer: self.timer,
             dma: self.dma,
+            sound: self.sound.create_state(),
+            bootrom_active: self.bootrom.is_active(),
         }
     }
 
+    /// Restores the hardware in place, the cartridge is restored separately.
+    pub fn load_state(&mut self, hardware_state: HardwareState, ppu_state: PPuState) {
+        self.interrupt_handler = hardware_state.interrupt_handler;
+        self.work_ram = hardware_state.work_ram;
+        self.hiram = hardware_state.hiram;
+        self.timer = hardware_state.timer;
+        self.dma = hardware_state.dma;
+        self.bootrom.set_active(hardware_state.bootrom_active);
+        self.sound.load_state(hardware_state.sound);
+        self.gpu.load_state(ppu_state);
+    }
+
     pub fn create_from_state(
         screen: T,
         cartridge: Box<dyn Cartridge + 'a>,
@@ -109,19 +179,9 @@ impl<'a, T: Screen> Hardware<'a, T> {
         hardware_state: HardwareState,
         ppu_state: PPuState,
     ) -> Hardware<'a, T> {
-        let ppu: Ppu<T> = Ppu::new_from_state(screen, ppu_state);
-        Hardware {
-            interrupt_handler: hardware_state.interrupt_handler,
-            work_ram: hardware_state.work_ram,
-            hiram: hardware_state.hiram,
-            timer: hardware_state.timer,
-            cartridge,
-            gpu: ppu,
-            bootrom: boot_rom,
-            dma: hardware_state.dma,
-            sound: Sound::new_dmg(player),
-            input_controller: InputController::new(),
-        }
+        let mut hardware = Hardware::create(screen, cartridge, boot_rom, player);
+        hardware.load_state(hardware_state, ppu_state);
+        hardware
     }
 }
 
@@ -133,6 +193,9 @@ pub struct HardwareState {
     pub hiram: HiramData,
     pub timer: Timer,
     pub dma: Dma,
+    pub sound: SoundState,
+    /// Whether 0x0000-0x00FF still maps the boot ROM.
+    pub bootrom_active: bool,
 }
 
 impl<'a, T: Screen> Interface for Hardware<'a, T> {
@@ -176,6 +239,33 @@ impl<'a, T: Screen> Interface for Hardware<'a, T> {
     }
     #[inline(always)] //IMPORTANT
     fn set_byte(&mut self, address: u16, value: u8) {
+        if self.observed() {
+            self.notify(address, value, true);
+        }
+        self.write_byte(address, value);
+    }
+
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.read_byte(address)
+    }
+
+    #[inline(always)] //IMPORTANT
+    fn get_byte(&mut self, address: u16) -> u8 {
+        let value = match address {
+            // The APU has to catch up before its registers can be read
+            0xFF10..=0xFF3F => self.sound.rb(address),
+            _ => self.read_byte(address),
+        };
+        if self.observed() {
+            self.notify(address, value, false);
+        }
+        value
+    }
+}
+
+impl<'a, T: Screen> Hardware<'a, T> {
+    #[inline(always)]
+    fn write_byte(&mut self, address: u16, value: u8) {
         match (address >> 8) as u8 {
             0x00 if self.bootrom.is_active() => {}
             0x00..=0x7f => self.cartridge.write_rom(address, value),
@@ -219,11 +309,15 @@ impl<'a, T: Screen> Interface for Hardware<'a, T> {
             },
         }
     }
-    #[inline(always)] //IMPORTANT
-    fn get_byte(&mut self, address: u16) -> u8 {
+
+    /// The bus as seen by the CPU, shared by reads and peeks so both always agree.
+    #[inline(always)]
+    fn read_byte(&self, address: u16) -> u8 {
         match (address >> 8) as u8 {
             0x00 if self.bootrom.is_active() => self.bootrom[address],
-            0x00..=0x7f => self.cartridge.read_rom(address),
+            0x00..=0x7f => self
+                .cheats
+                .patch_rom(address, self.cartridge.read_rom(address)),
 
             0x80..=0x9f => self.gpu.read_memory(address),
 
@@ -243,11 +337,12 @@ impl<'a, T: Screen> Interface for Hardware<'a, T> {
 
                     0x0f => self.interrupt_handler.get_interrupt_flag(),
 
-                    0x10..=0x3f => self.sound.rb(address),
+                    0x10..=0x3f => self.sound.read_register(address),
                     0x40 => self.gpu.get_control(),
                     0x41 => self.gpu.get_stat(),
                     0x42 => self.gpu.get_scroll_y(),
                     0x43 => self.gpu.get_scroll_x(),
+                    0x44 if self.doctor_ly => 0x90,
                     0x44 => self.gpu.get_current_line(),
                     0x45 => self.gpu.get_compare_line(),
                     0x46 => self.dma.source,
@@ -265,3 +360,23 @@ impl<'a, T: Screen> Interface for Hardware<'a, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Interface;
+    use crate::test_util::gameboy;
+
+    #[test]
+    fn peek_matches_reads() {
+        // JR -2
+        let mut gameboy = gameboy(&[0x18, 0xFE]);
+        for _ in 0..1000 {
+            gameboy.tick();
+        }
+        let hardware = &mut gameboy.cpu.interface;
+        for address in 0..=0xFFFF {
+            let peeked = hardware.peek_byte(address);
+            assert_eq!(peeked, hardware.get_byte(address), "{:#06x}", address);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Hook for tools following the bus traffic, such as tracers, coverage or achievements.
+//!
+//! The attached [`BusObserver`] sees every access made through the bus, DMA included, but
+//! not the peeks tools make nor the GameShark writes. Debugger watchpoints are fed through
+//! the same hook. `cycle` is the number of cycles emulated before the current instruction
+//! started, every access of an instruction carries the same timestamp.
+
+pub trait BusObserver {
+    fn read(&mut self, _address: u16, _value: u8, _cycle: u64) {}
+    fn write(&mut self, _address: u16, _value: u8, _cycle: u64) {}
+    /// An instruction at `address` is about to run, its opcode fetch is reported as a read.
+    fn execute(&mut self, _address: u16, _opcode: u8, _cycle: u64) {}
+    /// Reads of 0xFF00-0xFF7F and 0xFFFF, reported here instead of [`BusObserver::read`].
+    fn io_read(&mut self, _address: u16, _value: u8, _cycle: u64) {}
+    /// Writes of 0xFF00-0xFF7F and 0xFFFF, reported here instead of [`BusObserver::write`].
+    fn io_write(&mut self, _address: u16, _value: u8, _cycle: u64) {}
+}
+
+#[inline(always)]
+fn is_io(address: u16) -> bool {
+    matches!(address, 0xFF00..=0xFF7F | 0xFFFF)
+}
+
+pub(crate) fn notify_read(observer: &mut dyn BusObserver, address: u16, value: u8, cycle: u64) {
+    if is_io(address) {
+        observer.io_read(address, value, cycle);
+    } else {
+        observer.read(address, value, cycle);
+    }
+}
+
+pub(crate) fn notify_write(observer: &mut dyn BusObserver, address: u16, value: u8, cycle: u64) {
+    if is_io(address) {
+        observer.io_write(address, value, cycle);
+    } else {
+        observer.write(address, value, cycle);
+    }
+}
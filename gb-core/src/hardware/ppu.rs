@@ -131,30 +131,33 @@ impl<T: Screen> Ppu<T> {
             sprites: self.sprites,
         }
     }
+    /// Restores the PPU registers and video memory while keeping the screen.
+    pub fn load_state(&mut self, state: PPuState) {
+        self.background_palette = state.background_palette;
+        self.obj_palette0 = state.obj_palette0;
+        self.obj_palette1 = state.obj_palette1;
+        self.scanline = state.scanline;
+        self.video_ram = state.video_ram;
+        self.control = state.control;
+        self.stat = state.stat;
+        self.compare_line = state.compare_line;
+        self.scroll_x = state.scroll_x;
+        self.scroll_y = state.scroll_y;
+        self.background_priority = state.background_priority;
+        self.mode = state.mode;
+        self.window_x = state.window_x;
+        self.window_y = state.window_y;
+        self.cycle_counter = state.cycle_counter;
+        self.render_frame = state.render_frame;
+        self.skip_interval = state.skip_interval;
+        self.counter = state.counter;
+        self.sprites = state.sprites;
+    }
+
     pub fn new_from_state(screen: T, state: PPuState) -> Ppu<T> {
-        Ppu {
-            color_palette: RENDER_COLOR,
-            background_palette: state.background_palette,
-            obj_palette0: state.obj_palette0,
-            obj_palette1: state.obj_palette0,
-            scanline: state.scanline,
-            video_ram: state.video_ram,
-            control: state.control,
-            stat: state.stat,
-            compare_line: state.compare_line,
-            scroll_x: state.scroll_x,
-            scroll_y: state.scroll_y,
-            background_priority: state.background_priority,
-            mode: state.mode,
-            window_x: state.window_x,
-            window_y: state.window_y,
-            cycle_counter: state.cycle_counter,
-            screen,
-            render_frame: state.render_frame,
-            skip_interval: state.skip_interval,
-            counter: state.counter,
-            sprites: state.sprites,
-        }
+        let mut ppu = Ppu::new(screen);
+        ppu.load_state(state);
+        ppu
     }
 
     pub fn new(screen: T) -> Ppu<T> {
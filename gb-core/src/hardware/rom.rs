@@ -1,12 +1,16 @@
 use core::ops::Index;
-use core::str::FromStr;
 
 use crate::hardware::cartridge::{Cartridge, Mbc1Cartridge, ReadOnlyMemoryCartridge};
 
+use arrayvec::ArrayVec;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-use super::cartridge::Mbc3Cartridge;
+use super::cartridge::{
+    HuC1Cartridge, HuC3Cartridge, Mbc2Cartridge, Mbc3Cartridge, Mbc5Cartridge, Mbc7Cartridge,
+    PocketCameraCartridge,
+};
+use super::header::{CartridgeHeader, HeaderMismatch};
 
 #[cfg(feature = "std")]
 use std as core;
@@ -14,32 +18,76 @@ use std as core;
 #[cfg(not(feature = "std"))]
 use alloc::string::String;
 
-#[cfg(not(feature = "std"))]
-use alloc::format;
-
 #[cfg(not(feature = "std"))]
 use alloc::boxed::Box;
 
+const HEADER_END: usize = 0x150;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomError {
+    /// Cartridge type byte at 0x147 is not backed by any mapper.
+    UnsupportedMapper(u8),
+    /// ROM size byte at 0x148 is not a known size code.
+    InvalidRomSize(u8),
+    /// RAM size byte at 0x149 is not a known size code.
+    InvalidRamSize(u8),
+    /// The data is shorter than the header or the ROM size it declares.
+    Truncated { expected: usize, actual: usize },
+}
+
+impl core::fmt::Display for RomError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RomError::UnsupportedMapper(value) => {
+                write!(f, "unsupported cartridge type: {:#04x}", value)
+            }
+            RomError::InvalidRomSize(value) => write!(f, "invalid ROM size code: {:#04x}", value),
+            RomError::InvalidRamSize(value) => write!(f, "invalid RAM size code: {:#04x}", value),
+            RomError::Truncated { expected, actual } => {
+                write!(
+                    f,
+                    "truncated ROM: expected {} bytes, got {}",
+                    expected, actual
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RomError {}
+
 #[derive(FromPrimitive, Clone, Copy)]
 pub enum RomType {
     RomOnly = 0x00,
     MBC1 = 0x01,
     MBC1Ram = 0x02,
     MBC1RamBattery = 0x03,
+    MBC2 = 0x05,
+    MBC2Battery = 0x06,
     MBC3 = 0x11,
     MBC3Ram = 0x12,
     MBC3RamBattery = 0x13,
     MBC3TimerBattery = 0x0F,
     MBC3TimerRamBattery = 0x10,
+    MBC5 = 0x19,
+    MBC5Ram = 0x1A,
+    MBC5RamBattery = 0x1B,
+    MBC5Rumble = 0x1C,
+    MBC5RumbleRam = 0x1D,
+    MBC5RumbleRamBattery = 0x1E,
+    MBC7SensorRumbleRamBattery = 0x22,
+    PocketCamera = 0xFC,
+    HuC3 = 0xFE,
+    HuC1RamBattery = 0xFF,
 }
 
-fn rom_banks(v: u8) -> u8 {
-    if v <= 8 {
-        2 << v
-    } else {
-        0
-    }
-}
+pub const NINTENDO_LOGO_OFFSET: usize = 0x104;
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
 
 impl RomType {
     pub fn battery(&self) -> bool {
@@ -49,36 +97,77 @@ impl RomType {
             RomType::MBC3RamBattery => true,
             RomType::MBC1Ram => false,
             RomType::MBC1RamBattery => true,
+            RomType::MBC2 => false,
+            RomType::MBC2Battery => true,
             RomType::MBC3Ram => false,
             RomType::MBC3 => false,
             RomType::MBC3TimerBattery => true,
             RomType::MBC3TimerRamBattery => true,
+            RomType::MBC5 => false,
+            RomType::MBC5Ram => false,
+            RomType::MBC5RamBattery => true,
+            RomType::MBC5Rumble => false,
+            RomType::MBC5RumbleRam => false,
+            RomType::MBC5RumbleRamBattery => true,
+            RomType::MBC7SensorRumbleRamBattery => true,
+            RomType::PocketCamera => true,
+            RomType::HuC3 => true,
+            RomType::HuC1RamBattery => true,
         }
     }
 
+    pub fn timer(&self) -> bool {
+        matches!(
+            self,
+            RomType::MBC3TimerBattery | RomType::MBC3TimerRamBattery
+        )
+    }
+
+    pub fn rumble(&self) -> bool {
+        matches!(
+            self,
+            RomType::MBC5Rumble | RomType::MBC5RumbleRam | RomType::MBC5RumbleRamBattery
+        )
+    }
+
     pub fn to_cartridge<'a, RM: RomManager + 'a>(self, rom: Rom<RM>) -> Box<dyn Cartridge + 'a> {
         match self {
             RomType::RomOnly => Box::new(ReadOnlyMemoryCartridge::from_bytes(rom.data)),
             RomType::MBC1 => Box::new(Mbc1Cartridge::new(
                 rom.data,
                 rom.ram_size.banks(),
-                rom_banks(rom.rom_size as u8),
+                rom.rom_size.banks(),
+                self.battery(),
             )),
             RomType::MBC1Ram => Box::new(Mbc1Cartridge::new(
                 rom.data,
                 rom.ram_size.banks(),
-                rom_banks(rom.rom_size as u8),
+                rom.rom_size.banks(),
+                self.battery(),
             )),
             RomType::MBC1RamBattery => Box::new(Mbc1Cartridge::new(
                 rom.data,
                 rom.ram_size.banks(),
-                rom_banks(rom.rom_size as u8),
+                rom.rom_size.banks(),
+                self.battery(),
             )),
+            RomType::MBC2 => Box::new(Mbc2Cartridge::new(rom)),
+            RomType::MBC2Battery => Box::new(Mbc2Cartridge::new(rom)),
             RomType::MBC3RamBattery => Box::new(Mbc3Cartridge::new(rom)),
             RomType::MBC3Ram => Box::new(Mbc3Cartridge::new(rom)),
             RomType::MBC3 => Box::new(Mbc3Cartridge::new(rom)),
             RomType::MBC3TimerBattery => Box::new(Mbc3Cartridge::new(rom)),
             RomType::MBC3TimerRamBattery => Box::new(Mbc3Cartridge::new(rom)),
+            RomType::MBC5 => Box::new(Mbc5Cartridge::new(rom)),
+            RomType::MBC5Ram => Box::new(Mbc5Cartridge::new(rom)),
+            RomType::MBC5RamBattery => Box::new(Mbc5Cartridge::new(rom)),
+            RomType::MBC5Rumble => Box::new(Mbc5Cartridge::new(rom)),
+            RomType::MBC5RumbleRam => Box::new(Mbc5Cartridge::new(rom)),
+            RomType::MBC5RumbleRamBattery => Box::new(Mbc5Cartridge::new(rom)),
+            RomType::MBC7SensorRumbleRamBattery => Box::new(Mbc7Cartridge::new(rom)),
+            RomType::PocketCamera => Box::new(PocketCameraCartridge::new(rom)),
+            RomType::HuC3 => Box::new(HuC3Cartridge::new(rom)),
+            RomType::HuC1RamBattery => Box::new(HuC1Cartridge::new(rom)),
         }
     }
 }
@@ -86,8 +175,13 @@ impl RomType {
 pub trait RomManager:
     Index<usize, Output = u8> + Index<core::ops::Range<usize>, Output = [u8]>
 {
-    fn read_from_offset(&self, seek_offset: usize, index: usize, bank_number: u8) -> u8;
+    fn read_from_offset(&self, seek_offset: usize, index: usize, bank_number: u16) -> u8;
 
+    /// Length of the ROM in bytes.
+    fn size(&self) -> usize;
+
+    /// Wall clock time in microseconds since the UNIX epoch. Cartridge clocks synced to the
+    /// host follow it across sessions and it timestamps the clock saved in `.sav` files.
     fn clock(&self) -> u64;
 
     fn save(&mut self, game_title: &str, bank_index: u8, bank: &[u8]);
@@ -95,7 +189,7 @@ pub trait RomManager:
     fn load_to_bank(&mut self, game_title: &str, bank_index: u8, bank: &mut [u8]);
 }
 
-#[derive(FromPrimitive)]
+#[derive(FromPrimitive, Clone, Copy)]
 pub enum RomSize {
     _32KB = 0,
     _64KB = 1,
@@ -104,39 +198,56 @@ pub enum RomSize {
     _512KB = 4,
     _1MB = 5,
     _2MB = 6,
+    _4MB = 7,
+    _8MB = 8,
+    _1_1MB = 0x52,
+    _1_2MB = 0x53,
+    _1_5MB = 0x54,
 }
 
 impl RomSize {
-    pub fn expected_size(&mut self) -> u32 {
+    pub fn expected_size(&self) -> u32 {
+        self.banks() as u32 * 0x4000
+    }
+
+    /// Number of 16KB ROM banks.
+    pub fn banks(&self) -> u16 {
         match self {
-            RomSize::_32KB => 32 * 1024,
-            RomSize::_64KB => 64 * 1024,
-            RomSize::_128KB => 128 * 1024,
-            RomSize::_256KB => 256 * 1024,
-            RomSize::_512KB => 512 * 1024,
-            RomSize::_1MB => 1024 * 1024,
-            RomSize::_2MB => 2048 * 1024,
+            RomSize::_32KB => 2,
+            RomSize::_64KB => 4,
+            RomSize::_128KB => 8,
+            RomSize::_256KB => 16,
+            RomSize::_512KB => 32,
+            RomSize::_1MB => 64,
+            RomSize::_2MB => 128,
+            RomSize::_4MB => 256,
+            RomSize::_8MB => 512,
+            RomSize::_1_1MB => 72,
+            RomSize::_1_2MB => 80,
+            RomSize::_1_5MB => 96,
         }
     }
 }
 
-#[derive(FromPrimitive)]
+#[derive(FromPrimitive, Clone, Copy)]
 pub enum RamSize {
     NoRam = 0,
     _2KB = 0x01,
     _8KB = 0x02,
     _32KB = 0x03,
     _128KB = 0x04,
+    _64KB = 0x05,
 }
 
 impl RamSize {
     pub fn ram_size(&self) -> u32 {
         match self {
-            RamSize::NoRam => 0 * 1024,
+            RamSize::NoRam => 0,
             RamSize::_2KB => 2 * 1024,
             RamSize::_8KB => 8 * 1024,
             RamSize::_32KB => 32 * 1024,
             RamSize::_128KB => 128 * 1024,
+            RamSize::_64KB => 64 * 1024,
         }
     }
 
@@ -147,6 +258,7 @@ impl RamSize {
             RamSize::_8KB => 1,
             RamSize::_32KB => 4,
             RamSize::_128KB => 16,
+            RamSize::_64KB => 8,
         }
     }
 }
@@ -187,6 +299,7 @@ pub struct Rom<RM: RomManager> {
     pub model: Model,
     pub region: Region,
     pub title: String,
+    pub header: CartridgeHeader,
 }
 
 impl<'a, RM: RomManager + 'a> Rom<RM> {
@@ -195,36 +308,187 @@ impl<'a, RM: RomManager + 'a> Rom<RM> {
         rom_type.to_cartridge(self)
     }
 
-    pub fn from_bytes(bytes: RM) -> Self {
-        let rom_size = RomSize::from_u8(bytes[0x148]).unwrap();
-        let ram_size = RamSize::from_u8(bytes[0x149]).unwrap();
+    pub fn from_bytes(bytes: RM) -> Result<Self, RomError> {
+        if bytes.size() < HEADER_END {
+            return Err(RomError::Truncated {
+                expected: HEADER_END,
+                actual: bytes.size(),
+            });
+        }
+        let rom_type =
+            RomType::from_u8(bytes[0x147]).ok_or(RomError::UnsupportedMapper(bytes[0x147]))?;
+        let rom_size =
+            RomSize::from_u8(bytes[0x148]).ok_or(RomError::InvalidRomSize(bytes[0x148]))?;
+        let ram_size =
+            RamSize::from_u8(bytes[0x149]).ok_or(RomError::InvalidRamSize(bytes[0x149]))?;
+        if bytes.size() < rom_size.expected_size() as usize {
+            return Err(RomError::Truncated {
+                expected: rom_size.expected_size() as usize,
+                actual: bytes.size(),
+            });
+        }
         let model = Model::from_value(bytes[0x143]);
         let region = Region::from_value(bytes[0x14A]);
         let title = Rom::resolve_name(&bytes);
-        let rom_type = RomType::from_u8(bytes[0x147])
-            .expect(&format!("Rom type not matched: {}", bytes[0x147]));
-        Self {
+        let header = CartridgeHeader::from_bytes(&bytes);
+        Ok(Self {
             data: bytes,
-            rom_type: rom_type,
-            rom_size: rom_size,
-            ram_size: ram_size,
-            model: model,
-            region: region,
-            title: title,
-        }
+            rom_type,
+            rom_size,
+            ram_size,
+            model,
+            region,
+            title,
+            header,
+        })
+    }
+
+    /// Header fields that disagree with the ROM contents, usually a sign of a bad dump.
+    pub fn header_mismatches(&self) -> ArrayVec<HeaderMismatch, 4> {
+        self.header.mismatches(&self.data)
     }
 
     fn resolve_name(data: &RM) -> String {
         let new_cartridge = data[0x14b] == 0x33;
-        {
-            let slice = if new_cartridge {
-                &data[0x134..0x13f]
-            } else {
-                &data[0x134..0x143]
-            };
-            let utf8 = core::str::from_utf8(slice).unwrap();
-
-            String::from_str(utf8.trim_end_matches('\0')).unwrap()
+        let slice = if new_cartridge {
+            &data[0x134..0x13f]
+        } else {
+            &data[0x134..0x143]
+        };
+        let title = String::from_utf8_lossy(slice);
+
+        String::from(title.trim_end_matches('\0'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{rom_data, TestRom};
+
+    fn parse(data: TestRom) -> RomError {
+        match Rom::from_bytes(data) {
+            Ok(_) => panic!("the ROM should be rejected"),
+            Err(error) => error,
         }
     }
+
+    #[test]
+    fn rejects_unknown_header_codes() {
+        let mut data = rom_data(0x00, 0x00, 0x00, &[]);
+        data.data[0x147] = 0x20;
+        assert_eq!(parse(data), RomError::UnsupportedMapper(0x20));
+
+        let mut data = rom_data(0x00, 0x00, 0x00, &[]);
+        data.data[0x148] = 0x09;
+        assert_eq!(parse(data), RomError::InvalidRomSize(0x09));
+
+        let mut data = rom_data(0x00, 0x00, 0x00, &[]);
+        data.data[0x149] = 0x06;
+        assert_eq!(parse(data), RomError::InvalidRamSize(0x06));
+        assert_eq!(
+            RomError::InvalidRamSize(0x06).to_string(),
+            "invalid RAM size code: 0x06"
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let mut data = rom_data(0x00, 0x00, 0x00, &[]);
+        data.data.truncate(0x100);
+        assert_eq!(
+            parse(data),
+            RomError::Truncated {
+                expected: 0x150,
+                actual: 0x100
+            }
+        );
+
+        let mut data = rom_data(0x01, 0x01, 0x00, &[]);
+        data.data.truncate(0x8000);
+        assert_eq!(
+            parse(data),
+            RomError::Truncated {
+                expected: 0x10000,
+                actual: 0x8000
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_invalid_titles_lossily() {
+        let mut data = rom_data(0x00, 0x00, 0x00, &[]);
+        data.data[0x134..0x138].copy_from_slice(&[b'P', 0xFF, b'K', b'M']);
+        let rom = Rom::from_bytes(data).unwrap();
+        assert_eq!(rom.title, "P\u{FFFD}KM");
+        assert_eq!(rom.title, rom.header.title);
+    }
+
+    #[test]
+    fn maps_rom_size_codes_to_banks() {
+        let sizes = [
+            (0x00, 2),
+            (0x01, 4),
+            (0x02, 8),
+            (0x03, 16),
+            (0x04, 32),
+            (0x05, 64),
+            (0x06, 128),
+            (0x07, 256),
+            (0x08, 512),
+            (0x52, 72),
+            (0x53, 80),
+            (0x54, 96),
+        ];
+        for (code, banks) in sizes {
+            let size = RomSize::from_u8(code).unwrap();
+            assert_eq!(size.banks(), banks, "code {:#04x}", code);
+            assert_eq!(size.expected_size(), banks as u32 * 0x4000);
+        }
+        for code in [0x09, 0x51, 0x55, 0xFF] {
+            assert!(RomSize::from_u8(code).is_none(), "code {:#04x}", code);
+        }
+    }
+
+    #[test]
+    fn maps_ram_size_codes_to_banks() {
+        let sizes = [
+            (0x00, 0, 0),
+            (0x01, 1, 2 * 1024),
+            (0x02, 1, 8 * 1024),
+            (0x03, 4, 32 * 1024),
+            (0x04, 16, 128 * 1024),
+            (0x05, 8, 64 * 1024),
+        ];
+        for (code, banks, bytes) in sizes {
+            let size = RamSize::from_u8(code).unwrap();
+            assert_eq!(size.banks(), banks, "code {:#04x}", code);
+            assert_eq!(size.ram_size(), bytes, "code {:#04x}", code);
+        }
+        assert!(RamSize::from_u8(0x06).is_none());
+    }
+
+    #[test]
+    fn parses_the_largest_roms() {
+        let rom = Rom::from_bytes(rom_data(0x19, 0x08, 0x05, &[])).unwrap();
+        assert_eq!(rom.rom_size.banks(), 512);
+        assert_eq!(rom.ram_size.banks(), 8);
+    }
+
+    #[test]
+    fn reports_a_file_longer_than_the_header_says() {
+        let mut data = rom_data(0x00, 0x00, 0x00, &[]);
+        data.data.resize(0x10000, 0);
+        let rom = Rom::from_bytes(data).unwrap();
+        assert!(rom.header_mismatches().contains(&HeaderMismatch::RomSize {
+            declared: 0x8000,
+            actual: 0x10000
+        }));
+
+        let rom = Rom::from_bytes(rom_data(0x00, 0x00, 0x00, &[])).unwrap();
+        assert!(!rom
+            .header_mismatches()
+            .iter()
+            .any(|mismatch| matches!(mismatch, HeaderMismatch::RomSize { .. })));
+    }
 }
@@ -24,6 +24,7 @@ pub trait AudioPlayer {
     fn underflowed(&self) -> bool;
 }
 
+#[derive(Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct VolumeEnvelope {
     period: u8,
@@ -85,6 +86,7 @@ impl VolumeEnvelope {
     }
 }
 
+#[derive(Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct LengthCounter {
     enabled: bool,
@@ -163,7 +165,74 @@ struct SquareChannel {
     blip: Blip,
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SquareChannelState {
+    active: bool,
+    dac_enabled: bool,
+    duty: u8,
+    phase: u8,
+    length: LengthCounter,
+    frequency: u16,
+    period: u32,
+    last_amp: i32,
+    delay: u32,
+    has_sweep: bool,
+    sweep_enabled: bool,
+    sweep_frequency: u16,
+    sweep_delay: u8,
+    sweep_period: u8,
+    sweep_shift: u8,
+    sweep_negate: bool,
+    sweep_did_negate: bool,
+    volume_envelope: VolumeEnvelope,
+}
+
 impl SquareChannel {
+    fn create_state(&self) -> SquareChannelState {
+        SquareChannelState {
+            active: self.active,
+            dac_enabled: self.dac_enabled,
+            duty: self.duty,
+            phase: self.phase,
+            length: self.length,
+            frequency: self.frequency,
+            period: self.period,
+            last_amp: self.last_amp,
+            delay: self.delay,
+            has_sweep: self.has_sweep,
+            sweep_enabled: self.sweep_enabled,
+            sweep_frequency: self.sweep_frequency,
+            sweep_delay: self.sweep_delay,
+            sweep_period: self.sweep_period,
+            sweep_shift: self.sweep_shift,
+            sweep_negate: self.sweep_negate,
+            sweep_did_negate: self.sweep_did_negate,
+            volume_envelope: self.volume_envelope,
+        }
+    }
+
+    fn load_state(&mut self, state: SquareChannelState) {
+        self.active = state.active;
+        self.dac_enabled = state.dac_enabled;
+        self.duty = state.duty;
+        self.phase = state.phase;
+        self.length = state.length;
+        self.frequency = state.frequency;
+        self.period = state.period;
+        self.last_amp = state.last_amp;
+        self.delay = state.delay;
+        self.has_sweep = state.has_sweep;
+        self.sweep_enabled = state.sweep_enabled;
+        self.sweep_frequency = state.sweep_frequency;
+        self.sweep_delay = state.sweep_delay;
+        self.sweep_period = state.sweep_period;
+        self.sweep_shift = state.sweep_shift;
+        self.sweep_negate = state.sweep_negate;
+        self.sweep_did_negate = state.sweep_did_negate;
+        self.volume_envelope = state.volume_envelope;
+    }
+
     fn new(blip: Blip, with_sweep: bool) -> SquareChannel {
         SquareChannel {
             active: false,
@@ -365,7 +434,56 @@ struct WaveChannel {
     blip: Blip,
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WaveChannelState {
+    active: bool,
+    dac_enabled: bool,
+    length: LengthCounter,
+    frequency: u16,
+    period: u32,
+    last_amp: i32,
+    delay: u32,
+    volume_shift: u8,
+    waveram: [u8; 16],
+    current_wave: u8,
+    dmg_mode: bool,
+    sample_recently_accessed: bool,
+}
+
 impl WaveChannel {
+    fn create_state(&self) -> WaveChannelState {
+        WaveChannelState {
+            active: self.active,
+            dac_enabled: self.dac_enabled,
+            length: self.length,
+            frequency: self.frequency,
+            period: self.period,
+            last_amp: self.last_amp,
+            delay: self.delay,
+            volume_shift: self.volume_shift,
+            waveram: self.waveram,
+            current_wave: self.current_wave,
+            dmg_mode: self.dmg_mode,
+            sample_recently_accessed: self.sample_recently_accessed,
+        }
+    }
+
+    fn load_state(&mut self, state: WaveChannelState) {
+        self.active = state.active;
+        self.dac_enabled = state.dac_enabled;
+        self.length = state.length;
+        self.frequency = state.frequency;
+        self.period = state.period;
+        self.last_amp = state.last_amp;
+        self.delay = state.delay;
+        self.volume_shift = state.volume_shift;
+        self.waveram = state.waveram;
+        self.current_wave = state.current_wave;
+        self.dmg_mode = state.dmg_mode;
+        self.sample_recently_accessed = state.sample_recently_accessed;
+    }
+
     fn new(blip: Blip, dmg_mode: bool) -> WaveChannel {
         WaveChannel {
             active: false,
@@ -560,7 +678,50 @@ struct NoiseChannel {
     blip: Blip,
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseChannelState {
+    active: bool,
+    dac_enabled: bool,
+    reg_ff22: u8,
+    length: LengthCounter,
+    volume_envelope: VolumeEnvelope,
+    period: u32,
+    shift_width: u8,
+    state: u16,
+    delay: u32,
+    last_amp: i32,
+}
+
 impl NoiseChannel {
+    fn create_state(&self) -> NoiseChannelState {
+        NoiseChannelState {
+            active: self.active,
+            dac_enabled: self.dac_enabled,
+            reg_ff22: self.reg_ff22,
+            length: self.length,
+            volume_envelope: self.volume_envelope,
+            period: self.period,
+            shift_width: self.shift_width,
+            state: self.state,
+            delay: self.delay,
+            last_amp: self.last_amp,
+        }
+    }
+
+    fn load_state(&mut self, state: NoiseChannelState) {
+        self.active = state.active;
+        self.dac_enabled = state.dac_enabled;
+        self.reg_ff22 = state.reg_ff22;
+        self.length = state.length;
+        self.volume_envelope = state.volume_envelope;
+        self.period = state.period;
+        self.shift_width = state.shift_width;
+        self.state = state.state;
+        self.delay = state.delay;
+        self.last_amp = state.last_amp;
+    }
+
     fn new(blip: Blip) -> NoiseChannel {
         NoiseChannel {
             active: false,
@@ -664,6 +825,27 @@ impl NoiseChannel {
     }
 }
 
+/// Everything the APU needs to resume playback, the output buffers are rebuilt for the
+/// audio player in use.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SoundState {
+    on: bool,
+    time: u32,
+    prev_time: u32,
+    next_time: u32,
+    frame_step: u8,
+    channel1: SquareChannelState,
+    channel2: SquareChannelState,
+    channel3: WaveChannelState,
+    channel4: NoiseChannelState,
+    volume_left: u8,
+    volume_right: u8,
+    reg_vin_to_so: u8,
+    reg_ff25: u8,
+    dmg_mode: bool,
+}
+
 pub struct Sound {
     on: bool,
     time: u32,
@@ -722,9 +904,59 @@ impl Sound {
         }
     }
 
+    pub fn create_state(&self) -> SoundState {
+        SoundState {
+            on: self.on,
+            time: self.time,
+            prev_time: self.prev_time,
+            next_time: self.next_time,
+            frame_step: self.frame_step,
+            channel1: self.channel1.create_state(),
+            channel2: self.channel2.create_state(),
+            channel3: self.channel3.create_state(),
+            channel4: self.channel4.create_state(),
+            volume_left: self.volume_left,
+            volume_right: self.volume_right,
+            reg_vin_to_so: self.reg_vin_to_so,
+            reg_ff25: self.reg_ff25,
+            dmg_mode: self.dmg_mode,
+        }
+    }
+
+    pub fn new_from_state(player: Box<dyn AudioPlayer>, state: SoundState) -> Sound {
+        let mut sound = Sound::new_internal(player, state.dmg_mode);
+        sound.load_state(state);
+        sound
+    }
+
+    /// Restores the APU registers, the output buffers and audio player are kept.
+    pub fn load_state(&mut self, state: SoundState) {
+        self.on = state.on;
+        self.time = state.time;
+        self.prev_time = state.prev_time;
+        self.next_time = state.next_time;
+        self.frame_step = state.frame_step;
+        self.channel1.load_state(state.channel1);
+        self.channel2.load_state(state.channel2);
+        self.channel3.load_state(state.channel3);
+        self.channel4.load_state(state.channel4);
+        self.volume_left = state.volume_left;
+        self.volume_right = state.volume_right;
+        self.reg_vin_to_so = state.reg_vin_to_so;
+        self.reg_ff25 = state.reg_ff25;
+        self.need_sync = false;
+        self.dmg_mode = state.dmg_mode;
+        self.clear_buffers();
+    }
+
     pub fn rb(&mut self, a: u16) -> u8 {
         self.run();
-        let v = match a {
+        self.read_register(a)
+    }
+
+    /// Register value as of the last time the APU ran, without catching up.
+    pub fn read_register(&self, a: u16) -> u8 {
+        match a {
             0xFF10..=0xFF14 => self.channel1.rb(a),
             0xFF16..=0xFF19 => self.channel2.rb(a),
             0xFF1A..=0xFF1E => self.channel3.rb(a),
@@ -741,8 +973,7 @@ impl Sound {
             }
             0xFF30..=0xFF3F => self.channel3.rb(a),
             _ => 0xFF,
-        };
-        return v;
+        }
     }
 
     pub fn wb(&mut self, a: u16, v: u8) {
@@ -973,3 +1204,78 @@ fn create_blipbuf(samples_rate: u32) -> Blip {
     blipbuf.set_rates(CLOCKS_PER_SECOND as f64, samples_rate as f64);
     blipbuf
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::NullPlayer;
+
+    const WAVE: [u8; 16] = [
+        0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0xFE, 0xDC, 0xBA, 0x98, 0x76, 0x54, 0x32,
+        0x10,
+    ];
+
+    fn encoded(sound: &Sound) -> Vec<u8> {
+        let mut data = Vec::new();
+        crate::savestate::binary::to_bytes(&sound.create_state(), &mut data).unwrap();
+        data
+    }
+
+    fn registers(sound: &mut Sound) -> Vec<u8> {
+        (0xFF10..=0xFF3F).map(|address| sound.rb(address)).collect()
+    }
+
+    #[test]
+    fn state_round_trip_in_the_middle_of_notes() {
+        let mut sound = Sound::new_dmg(Box::new(NullPlayer));
+        sound.wb(0xFF26, 0x80);
+        sound.wb(0xFF24, 0x77);
+        sound.wb(0xFF25, 0xFF);
+        for (offset, value) in WAVE.iter().enumerate() {
+            sound.wb(0xFF30 + offset as u16, *value);
+        }
+        // Falling sweep, then every channel with a length counter and envelopes that fade
+        let writes = [
+            (0xFF10, 0x1A),
+            (0xFF11, 0x80 | 0x30),
+            (0xFF12, 0xF1),
+            (0xFF13, 0x00),
+            (0xFF14, 0xC4),
+            (0xFF16, 0x40 | 0x20),
+            (0xFF17, 0xA2),
+            (0xFF18, 0x80),
+            (0xFF19, 0xC6),
+            (0xFF1A, 0x80),
+            (0xFF1B, 0xE0),
+            (0xFF1C, 0x20),
+            (0xFF1D, 0x40),
+            (0xFF1E, 0xC7),
+            (0xFF20, 0x38),
+            (0xFF21, 0x91),
+            (0xFF22, 0x24),
+            (0xFF23, 0xC0),
+        ];
+        for (address, value) in writes {
+            sound.wb(address, value);
+        }
+        sound.do_cycle(10 * CLOCKS_PER_FRAME);
+        assert_eq!(sound.rb(0xFF26), 0xFF);
+
+        let mut restored = Sound::new_from_state(Box::new(NullPlayer), sound.create_state());
+        assert_eq!(registers(&mut restored), registers(&mut sound));
+        assert_eq!(encoded(&restored), encoded(&sound));
+
+        // Both keep playing the same notes until every length counter runs out
+        for _ in 0..60 {
+            sound.do_cycle(CLOCKS_PER_FRAME);
+            restored.do_cycle(CLOCKS_PER_FRAME);
+            assert_eq!(registers(&mut restored), registers(&mut sound));
+            assert_eq!(encoded(&restored), encoded(&sound));
+        }
+        assert_eq!(restored.rb(0xFF26), 0xF0);
+        let wave: Vec<u8> = (0xFF30..=0xFF3F)
+            .map(|address| restored.rb(address))
+            .collect();
+        assert_eq!(wave, WAVE);
+    }
+}
@@ -3,10 +3,23 @@
 #[cfg(not(feature = "std"))]
 extern crate alloc;
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 mod cpu;
+pub use cpu::disassembler;
+pub mod debugger;
 pub mod gameboy;
 pub mod hardware;
 mod memory;
+pub mod patch;
+#[cfg(feature = "serde")]
+pub mod rewind;
+#[cfg(feature = "serde")]
+pub mod savestate;
+pub mod search;
+#[cfg(test)]
+mod test_util;
+pub mod trace;
 mod util;
 
 #[cfg(feature = "defmt-log")]
@@ -33,14 +46,17 @@ macro_rules! warn {
     ($($arg:tt)+) => {};
 }
 
-// static mut ENABLE_LOG: LazyLock<Arc<Mutex<bool>>> =
-//     std::sync::LazyLock::new(|| Arc::new(Mutex::new(false)));
+static ENABLE_LOG: AtomicBool = AtomicBool::new(false);
 
 #[inline(always)]
 pub fn is_log_enabled() -> bool {
-    // let enable_log = unsafe { crate::ENABLE_LOG.lock().unwrap() };
-    // *enable_log
-    false
+    ENABLE_LOG.load(Ordering::Relaxed)
+}
+#[inline(always)]
+pub fn enable_logging() {
+    ENABLE_LOG.store(true, Ordering::Relaxed)
 }
 #[inline(always)]
-pub fn enable_logging() {}
+pub fn disable_logging() {
+    ENABLE_LOG.store(false, Ordering::Relaxed)
+}
@@ -0,0 +1,642 @@
+//! ROM patches in the IPS, UPS and BPS formats used by translations and ROM hacks.
+//!
+//! Patches are applied to a ROM buffer before it is handed to [`Rom::from_bytes`]. UPS and
+//! BPS carry CRC32 checksums of the source, the target and the patch itself, all three are
+//! checked so a patch is never applied to the wrong dump.
+//!
+//! [`Rom::from_bytes`]: crate::hardware::rom::Rom::from_bytes
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: usize = 0x454F46;
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+// Source, target and patch checksums
+const FOOTER_SIZE: usize = 12;
+/// Largest ROM a cartridge header can declare, 512 banks of 16KB.
+pub const MAX_ROM_SIZE: usize = 512 * 0x4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Ups,
+    Bps,
+}
+
+impl PatchFormat {
+    pub const ALL: [PatchFormat; 3] = [PatchFormat::Ips, PatchFormat::Ups, PatchFormat::Bps];
+
+    pub fn detect(patch: &[u8]) -> Option<Self> {
+        if patch.starts_with(IPS_MAGIC) {
+            Some(PatchFormat::Ips)
+        } else if patch.starts_with(UPS_MAGIC) {
+            Some(PatchFormat::Ups)
+        } else if patch.starts_with(BPS_MAGIC) {
+            Some(PatchFormat::Bps)
+        } else {
+            None
+        }
+    }
+
+    /// File extension patches of this format are distributed with.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PatchFormat::Ips => "ips",
+            PatchFormat::Ups => "ups",
+            PatchFormat::Bps => "bps",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The patch does not start with a known magic.
+    UnknownFormat,
+    /// The patch ended in the middle of a record.
+    UnexpectedEnd,
+    /// A record points outside of the ROM it produces.
+    InvalidOffset,
+    /// The patched ROM would be larger than [`MAX_ROM_SIZE`].
+    TooLarge(usize),
+    /// The ROM is not the one the patch was made for.
+    SourceMismatch { expected: u32, actual: u32 },
+    /// The patched ROM does not match the checksum stored in the patch.
+    TargetMismatch { expected: u32, actual: u32 },
+    /// The patch file itself is damaged.
+    PatchMismatch { expected: u32, actual: u32 },
+}
+
+impl core::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "unknown patch format"),
+            PatchError::UnexpectedEnd => write!(f, "truncated patch"),
+            PatchError::InvalidOffset => write!(f, "patch writes outside of the ROM"),
+            PatchError::TooLarge(size) => {
+                write!(
+                    f,
+                    "patched ROM would be {} bytes, more than any cartridge",
+                    size
+                )
+            }
+            PatchError::SourceMismatch { expected, actual } => write!(
+                f,
+                "patch expects a ROM with CRC32 {:08x}, found {:08x}",
+                expected, actual
+            ),
+            PatchError::TargetMismatch { expected, actual } => write!(
+                f,
+                "patched ROM has CRC32 {:08x}, expected {:08x}",
+                actual, expected
+            ),
+            PatchError::PatchMismatch { expected, actual } => write!(
+                f,
+                "corrupted patch, CRC32 {:08x} instead of {:08x}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+/// Applies a patch of any supported format to `rom`, returning the patched ROM.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(rom, patch),
+        Some(PatchFormat::Ups) => apply_ups(rom, patch),
+        Some(PatchFormat::Bps) => apply_bps(rom, patch),
+        None => Err(PatchError::UnknownFormat),
+    }
+}
+
+/// IPS records are a 24 bit offset and a 16 bit length followed by the data, a zero length
+/// marks a run of one repeated byte. An optional 24 bit size after `EOF` truncates the ROM.
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(IPS_MAGIC) {
+        return Err(PatchError::UnknownFormat);
+    }
+    let mut reader = PatchReader::new(&patch[IPS_MAGIC.len()..]);
+    let mut output = rom.to_vec();
+    loop {
+        let offset = reader.read_be(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+        let length = reader.read_be(2)?;
+        if length == 0 {
+            let length = reader.read_be(2)?;
+            let value = reader.read_byte()?;
+            write_ips(&mut output, offset, &[value].repeat(length))?;
+        } else {
+            let data = reader.read_slice(length)?;
+            write_ips(&mut output, offset, data)?;
+        }
+    }
+    if reader.remaining() >= 3 {
+        let size = reader.read_be(3)?;
+        output.truncate(size);
+    }
+    Ok(output)
+}
+
+fn write_ips(output: &mut Vec<u8>, offset: usize, data: &[u8]) -> Result<(), PatchError> {
+    let end = offset + data.len();
+    if output.len() < end {
+        check_size(end)?;
+        output.resize(end, 0);
+    }
+    output[offset..end].copy_from_slice(data);
+    Ok(())
+}
+
+/// The sizes come from the patch, they are checked before anything is allocated.
+fn check_size(size: usize) -> Result<(), PatchError> {
+    if size > MAX_ROM_SIZE {
+        return Err(PatchError::TooLarge(size));
+    }
+    Ok(())
+}
+
+/// UPS hunks skip a relative number of bytes then XOR the source until a zero byte.
+pub fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(UPS_MAGIC) {
+        return Err(PatchError::UnknownFormat);
+    }
+    let checksums = Checksums::read(patch)?;
+    checksums.check_patch(patch)?;
+    checksums.check_source(rom)?;
+
+    let body = &patch[UPS_MAGIC.len()..patch.len() - FOOTER_SIZE];
+    let mut reader = PatchReader::new(body);
+    let source_size = reader.read_number()?;
+    let target_size = reader.read_number()?;
+    check_size(target_size)?;
+    if rom.len() != source_size {
+        return Err(PatchError::SourceMismatch {
+            expected: checksums.source,
+            actual: crc32(rom),
+        });
+    }
+
+    let mut output = rom.to_vec();
+    output.resize(target_size, 0);
+    let mut offset = 0;
+    while reader.remaining() > 0 {
+        offset += reader.read_number()?;
+        loop {
+            let value = reader.read_byte()?;
+            if value == 0 {
+                offset += 1;
+                break;
+            }
+            *output.get_mut(offset).ok_or(PatchError::InvalidOffset)? ^= value;
+            offset += 1;
+        }
+    }
+
+    checksums.check_target(&output)?;
+    Ok(output)
+}
+
+/// BPS builds the target from four actions: copy the source in place, copy literal bytes
+/// from the patch, and copy from a relative offset into either the source or the target.
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(BPS_MAGIC) {
+        return Err(PatchError::UnknownFormat);
+    }
+    let checksums = Checksums::read(patch)?;
+    checksums.check_patch(patch)?;
+    checksums.check_source(rom)?;
+
+    let body = &patch[BPS_MAGIC.len()..patch.len() - FOOTER_SIZE];
+    let mut reader = PatchReader::new(body);
+    let source_size = reader.read_number()?;
+    let target_size = reader.read_number()?;
+    check_size(target_size)?;
+    let metadata_size = reader.read_number()?;
+    reader.read_slice(metadata_size)?;
+    if rom.len() != source_size {
+        return Err(PatchError::SourceMismatch {
+            expected: checksums.source,
+            actual: crc32(rom),
+        });
+    }
+
+    let mut output = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+    while reader.remaining() > 0 {
+        let action = reader.read_number()?;
+        let length = (action >> 2) + 1;
+        if output.len() + length > target_size {
+            return Err(PatchError::InvalidOffset);
+        }
+        match action & 0x03 {
+            // Source read
+            0 => {
+                let start = output.len();
+                let data = rom
+                    .get(start..start + length)
+                    .ok_or(PatchError::InvalidOffset)?;
+                output.extend_from_slice(data);
+            }
+            // Target read
+            1 => output.extend_from_slice(reader.read_slice(length)?),
+            // Source copy
+            2 => {
+                source_offset = relative_offset(source_offset, reader.read_number()?)?;
+                let data = rom
+                    .get(source_offset..source_offset + length)
+                    .ok_or(PatchError::InvalidOffset)?;
+                output.extend_from_slice(data);
+                source_offset += length;
+            }
+            // Target copy, byte by byte since the range may overlap what is being written
+            _ => {
+                target_offset = relative_offset(target_offset, reader.read_number()?)?;
+                for _ in 0..length {
+                    let value = *output.get(target_offset).ok_or(PatchError::InvalidOffset)?;
+                    output.push(value);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    if output.len() != target_size {
+        return Err(PatchError::UnexpectedEnd);
+    }
+
+    checksums.check_target(&output)?;
+    Ok(output)
+}
+
+/// BPS relative offsets store the sign in the lowest bit.
+fn relative_offset(offset: usize, data: usize) -> Result<usize, PatchError> {
+    let distance = data >> 1;
+    if data & 1 != 0 {
+        offset.checked_sub(distance)
+    } else {
+        offset.checked_add(distance)
+    }
+    .ok_or(PatchError::InvalidOffset)
+}
+
+struct Checksums {
+    source: u32,
+    target: u32,
+    patch: u32,
+}
+
+impl Checksums {
+    fn read(patch: &[u8]) -> Result<Self, PatchError> {
+        if patch.len() < 4 + FOOTER_SIZE {
+            return Err(PatchError::UnexpectedEnd);
+        }
+        let footer = &patch[patch.len() - FOOTER_SIZE..];
+        let read = |index: usize| {
+            let mut value = [0; 4];
+            value.copy_from_slice(&footer[index * 4..index * 4 + 4]);
+            u32::from_le_bytes(value)
+        };
+        Ok(Self {
+            source: read(0),
+            target: read(1),
+            patch: read(2),
+        })
+    }
+
+    fn check_patch(&self, patch: &[u8]) -> Result<(), PatchError> {
+        let actual = crc32(&patch[..patch.len() - 4]);
+        if actual != self.patch {
+            return Err(PatchError::PatchMismatch {
+                expected: self.patch,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_source(&self, rom: &[u8]) -> Result<(), PatchError> {
+        let actual = crc32(rom);
+        if actual != self.source {
+            return Err(PatchError::SourceMismatch {
+                expected: self.source,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_target(&self, output: &[u8]) -> Result<(), PatchError> {
+        let actual = crc32(output);
+        if actual != self.target {
+            return Err(PatchError::TargetMismatch {
+                expected: self.target,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    fn read_slice(&mut self, length: usize) -> Result<&'a [u8], PatchError> {
+        if self.data.len() < length {
+            return Err(PatchError::UnexpectedEnd);
+        }
+        let (head, tail) = self.data.split_at(length);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, PatchError> {
+        self.read_slice(1).map(|byte| byte[0])
+    }
+
+    fn read_be(&mut self, length: usize) -> Result<usize, PatchError> {
+        let bytes = self.read_slice(length)?;
+        Ok(bytes
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as usize))
+    }
+
+    /// Variable length number shared by UPS and BPS, seven bits per byte with the high bit
+    /// ending the number and an implicit increment on every continuation.
+    fn read_number(&mut self) -> Result<usize, PatchError> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.read_byte()?;
+            value = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|part| value.checked_add(part))
+                .ok_or(PatchError::InvalidOffset)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or(PatchError::InvalidOffset)?;
+            value = value.checked_add(shift).ok_or(PatchError::InvalidOffset)?;
+        }
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 {
+                (value >> 1) ^ 0xEDB8_8320
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[index] = value;
+        index += 1;
+    }
+    table
+}
+
+/// CRC32 as used by zip and by the UPS and BPS footers.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(mut value: usize, output: &mut Vec<u8>) {
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                output.push(0x80 | low);
+                return;
+            }
+            output.push(low);
+            value -= 1;
+        }
+    }
+
+    /// BPS action `kind` covering `length` bytes.
+    fn action(length: usize, kind: usize, output: &mut Vec<u8>) {
+        number(((length - 1) << 2) | kind, output);
+    }
+
+    fn footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    /// Encodes the XOR of `source` and `target` as UPS hunks.
+    fn ups(source: &[u8], target: &[u8]) -> Vec<u8> {
+        let mut patch = UPS_MAGIC.to_vec();
+        number(source.len(), &mut patch);
+        number(target.len(), &mut patch);
+        let xor = |index: usize| source.get(index).unwrap_or(&0) ^ target[index];
+        let mut last = 0;
+        let mut index = 0;
+        while index < target.len() {
+            if xor(index) == 0 {
+                index += 1;
+                continue;
+            }
+            number(index - last, &mut patch);
+            while index < target.len() && xor(index) != 0 {
+                patch.push(xor(index));
+                index += 1;
+            }
+            patch.push(0);
+            index += 1;
+            last = index;
+        }
+        footer(patch, source, target)
+    }
+
+    fn bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = BPS_MAGIC.to_vec();
+        number(source.len(), &mut patch);
+        number(target.len(), &mut patch);
+        number(0, &mut patch);
+        patch.extend_from_slice(actions);
+        footer(patch, source, target)
+    }
+
+    fn source() -> Vec<u8> {
+        (0..64).collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(PatchFormat::detect(b"PATCHEOF"), Some(PatchFormat::Ips));
+        assert_eq!(PatchFormat::detect(b"UPS1"), Some(PatchFormat::Ups));
+        assert_eq!(PatchFormat::detect(b"BPS1"), Some(PatchFormat::Bps));
+        assert_eq!(PatchFormat::detect(b"PK\x03\x04"), None);
+        assert_eq!(
+            apply(&source(), b"PK\x03\x04"),
+            Err(PatchError::UnknownFormat)
+        );
+    }
+
+    #[test]
+    fn ips_records_runs_and_growth() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x03, 0xA1, 0xA2, 0xA3]);
+        // Run of four 0x77
+        patch.extend_from_slice(&[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x04, 0x77]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x41, 0x00, 0x02, 0xB1, 0xB2]);
+        patch.extend_from_slice(b"EOF");
+
+        let mut expected = source();
+        expected[2..5].copy_from_slice(&[0xA1, 0xA2, 0xA3]);
+        expected[8..12].fill(0x77);
+        expected.extend_from_slice(&[0x00, 0xB1, 0xB2]);
+        assert_eq!(apply(&source(), &patch), Ok(expected.clone()));
+
+        patch.extend_from_slice(&[0x00, 0x00, 0x10]);
+        assert_eq!(apply(&source(), &patch), Ok(expected[..16].to_vec()));
+
+        assert_eq!(
+            apply(&source(), &patch[..patch.len() - 9]),
+            Err(PatchError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn ups_round_trip() {
+        let mut target = source();
+        target[3] = 0xFF;
+        target[10..20].fill(0);
+        target.extend_from_slice(b"longer");
+        assert_eq!(
+            apply(&source(), &ups(&source(), &target)),
+            Ok(target.clone())
+        );
+
+        let shorter = target[..40].to_vec();
+        assert_eq!(apply(&source(), &ups(&source(), &shorter)), Ok(shorter));
+    }
+
+    #[test]
+    fn bps_actions() {
+        let source = source();
+        let mut target = source[..16].to_vec();
+        target.extend_from_slice(b"HELLO");
+        target.extend_from_slice(&source[40..48]);
+        target.extend_from_slice(&[0xAA; 8]);
+        target.extend_from_slice(&source[2..5]);
+
+        let mut actions = Vec::new();
+        action(16, 0, &mut actions);
+        action(5, 1, &mut actions);
+        actions.extend_from_slice(b"HELLO");
+        // Source copy from +40
+        action(8, 2, &mut actions);
+        number(40 << 1, &mut actions);
+        action(1, 1, &mut actions);
+        actions.push(0xAA);
+        // Target copy overlapping what it writes, from +29
+        action(7, 3, &mut actions);
+        number(29 << 1, &mut actions);
+        // Source copy from -46
+        action(3, 2, &mut actions);
+        number((46 << 1) | 1, &mut actions);
+
+        assert_eq!(apply(&source, &bps(&source, &target, &actions)), Ok(target));
+    }
+
+    #[test]
+    fn checksum_mismatches() {
+        let mut target = source();
+        target[0] = 0x42;
+        let mut actions = Vec::new();
+        action(1, 1, &mut actions);
+        actions.push(0x42);
+        action(63, 0, &mut actions);
+        for patch in [ups(&source(), &target), bps(&source(), &target, &actions)] {
+            assert_eq!(apply(&source(), &patch), Ok(target.clone()));
+
+            let mut damaged = patch.clone();
+            damaged[6] ^= 0x01;
+            assert!(matches!(
+                apply(&source(), &damaged),
+                Err(PatchError::PatchMismatch { .. })
+            ));
+
+            let mut other = source();
+            other[63] = 0;
+            assert_eq!(
+                apply(&other, &patch),
+                Err(PatchError::SourceMismatch {
+                    expected: crc32(&source()),
+                    actual: crc32(&other),
+                })
+            );
+
+            // Valid patch checksum over a wrong target checksum
+            let mut body = patch[..patch.len() - FOOTER_SIZE].to_vec();
+            body.extend_from_slice(&crc32(&source()).to_le_bytes());
+            body.extend_from_slice(&0x1234_5678u32.to_le_bytes());
+            body.extend_from_slice(&crc32(&body).to_le_bytes());
+            assert_eq!(
+                apply(&source(), &body),
+                Err(PatchError::TargetMismatch {
+                    expected: 0x1234_5678,
+                    actual: crc32(&target),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_targets() {
+        for magic in [UPS_MAGIC, BPS_MAGIC] {
+            let mut patch = magic.to_vec();
+            number(64, &mut patch);
+            number(1 << 40, &mut patch);
+            number(0, &mut patch);
+            let patch = footer(patch, &source(), &[]);
+            assert_eq!(apply(&source(), &patch), Err(PatchError::TooLarge(1 << 40)));
+        }
+
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        patch.extend_from_slice(&[0; 0xFFFF]);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(
+            apply(&source(), &patch),
+            Err(PatchError::TooLarge(0xFFFFFF + 0xFFFF))
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Rewind history built on periodic save states.
+//!
+//! The newest snapshot is kept whole and every older one is stored as the run length encoded
+//! XOR against the snapshot that followed it, so consecutive frames that only touch a few
+//! bytes of RAM cost little more than the run headers.
+
+use crate::gameboy::{GameBoy, GameBoyState};
+use crate::hardware::Screen;
+use crate::savestate::{binary, rle, SaveStateError};
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::VecDeque, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+
+pub struct Rewind {
+    capacity: usize,
+    interval: u32,
+    frames_since_snapshot: u32,
+    latest: Vec<u8>,
+    history: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    /// Keeps up to `capacity` older snapshots, one taken every `interval` frames.
+    pub fn new(capacity: usize, interval: u32) -> Rewind {
+        Rewind {
+            capacity,
+            interval: interval.max(1),
+            frames_since_snapshot: 0,
+            latest: Vec::new(),
+            history: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Must be called once per emulated frame, takes a snapshot when the interval elapses.
+    pub fn record_frame<S: Screen>(&mut self, gameboy: &GameBoy<S>) -> Result<(), SaveStateError> {
+        self.frames_since_snapshot += 1;
+        if !self.latest.is_empty() && self.frames_since_snapshot < self.interval {
+            return Ok(());
+        }
+        self.frames_since_snapshot = 0;
+        self.push(&gameboy.create_state())
+    }
+
+    fn push(&mut self, state: &GameBoyState) -> Result<(), SaveStateError> {
+        let mut snapshot = Vec::with_capacity(self.latest.len());
+        binary::to_bytes(state, &mut snapshot)?;
+        if !self.latest.is_empty() && self.capacity > 0 {
+            if self.history.len() == self.capacity {
+                self.history.pop_front();
+            }
+            let mut delta = Vec::new();
+            encode_delta(&self.latest, &snapshot, &mut delta);
+            self.history.push_back(delta);
+        }
+        self.latest = snapshot;
+        Ok(())
+    }
+
+    /// Steps back at least `frames` frames, rounded up to the snapshot before that point, and
+    /// restores the GameBoy in place. Returns how many frames were actually rewound, fewer
+    /// than asked when the history runs out.
+    pub fn rewind<S: Screen>(
+        &mut self,
+        gameboy: &mut GameBoy<S>,
+        frames: u32,
+    ) -> Result<u32, SaveStateError> {
+        if self.latest.is_empty() {
+            return Ok(0);
+        }
+        // The newest snapshot was taken a few frames ago already
+        let mut rewound = self.frames_since_snapshot;
+        while rewound < frames {
+            let delta = match self.history.pop_back() {
+                Some(delta) => delta,
+                None => break,
+            };
+            self.latest = decode_delta(&self.latest, &delta)?;
+            rewound += self.interval;
+        }
+        gameboy.load_state(binary::from_bytes(&self.latest)?);
+        self.frames_since_snapshot = 0;
+        Ok(rewound)
+    }
+
+    /// Number of frames that can still be rewound.
+    pub fn available_frames(&self) -> u32 {
+        if self.latest.is_empty() {
+            return 0;
+        }
+        self.frames_since_snapshot + self.history.len() as u32 * self.interval
+    }
+
+    pub fn clear(&mut self) {
+        self.latest.clear();
+        self.history.clear();
+        self.frames_since_snapshot = 0;
+    }
+}
+
+fn encode_delta(older: &[u8], newer: &[u8], output: &mut Vec<u8>) {
+    output.extend_from_slice(&(older.len() as u32).to_le_bytes());
+    let xored: Vec<u8> = older
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| byte ^ newer.get(index).copied().unwrap_or(0))
+        .collect();
+    rle::compress(&xored, output);
+}
+
+fn decode_delta(newer: &[u8], delta: &[u8]) -> Result<Vec<u8>, SaveStateError> {
+    let length = delta.get(..4).ok_or(SaveStateError::UnexpectedEnd)?;
+    let length = u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize;
+    let mut older = Vec::with_capacity(length);
+    rle::decompress(&delta[4..], &mut older, length)?;
+    if older.len() != length {
+        return Err(SaveStateError::Corrupted);
+    }
+    for (index, byte) in older.iter_mut().enumerate() {
+        *byte ^= newer.get(index).copied().unwrap_or(0);
+    }
+    Ok(older)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{gameboy, NullScreen};
+
+    /// Runs `frames` frames of a program counting in WRAM, recording each one, and returns
+    /// the state after every frame.
+    fn record(
+        rewind: &mut Rewind,
+        gameboy: &mut GameBoy<NullScreen>,
+        frames: usize,
+    ) -> Vec<Vec<u8>> {
+        (0..frames)
+            .map(|_| {
+                gameboy.run_to_frame();
+                rewind.record_frame(gameboy).unwrap();
+                state(gameboy)
+            })
+            .collect()
+    }
+
+    fn state(gameboy: &GameBoy<NullScreen>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        binary::to_bytes(&gameboy.create_state(), &mut bytes).unwrap();
+        bytes
+    }
+
+    fn counter() -> GameBoy<'static, NullScreen> {
+        // LD HL,$C000; INC (HL); JR -3
+        gameboy(&[0x21, 0x00, 0xC0, 0x34, 0x18, 0xFD])
+    }
+
+    #[test]
+    fn rewinds_to_recorded_states() {
+        let mut gameboy = counter();
+        let mut rewind = Rewind::new(10, 2);
+        // Snapshots of frames 0, 2, 4, 6 and 8, frame 9 is one past the newest
+        let states = record(&mut rewind, &mut gameboy, 10);
+        assert_eq!(rewind.available_frames(), 9);
+
+        assert_eq!(rewind.rewind(&mut gameboy, 1).unwrap(), 1);
+        assert_eq!(state(&gameboy), states[8]);
+        assert_eq!(rewind.rewind(&mut gameboy, 3).unwrap(), 4);
+        assert_eq!(state(&gameboy), states[4]);
+
+        // Recording again continues from the restored frame
+        let replayed = record(&mut rewind, &mut gameboy, 2);
+        assert_eq!(replayed[1], states[6]);
+        assert_eq!(rewind.available_frames(), 6);
+    }
+
+    #[test]
+    fn stops_when_the_history_runs_out() {
+        let mut gameboy = counter();
+        let mut rewind = Rewind::new(10, 3);
+        assert_eq!(rewind.rewind(&mut gameboy, 5).unwrap(), 0);
+
+        // Snapshots of frames 0 and 3
+        let states = record(&mut rewind, &mut gameboy, 5);
+        assert_eq!(rewind.rewind(&mut gameboy, 100).unwrap(), 4);
+        assert_eq!(state(&gameboy), states[0]);
+        assert_eq!(rewind.available_frames(), 0);
+        assert_eq!(rewind.rewind(&mut gameboy, 1).unwrap(), 0);
+        assert_eq!(state(&gameboy), states[0]);
+
+        rewind.clear();
+        assert_eq!(rewind.available_frames(), 0);
+    }
+
+    #[test]
+    fn drops_the_oldest_snapshots_past_capacity() {
+        let mut gameboy = counter();
+        let mut rewind = Rewind::new(2, 1);
+        let states = record(&mut rewind, &mut gameboy, 5);
+        assert_eq!(rewind.history.len(), 2);
+        assert_eq!(rewind.rewind(&mut gameboy, 10).unwrap(), 2);
+        assert_eq!(state(&gameboy), states[2]);
+    }
+}
@@ -0,0 +1,752 @@
+//! Compact serde format used by save states: integers and floats are fixed width little
+//! endian, lengths and enum variants are `u32`. The format is not self describing so data
+//! can only be read back into the type that wrote it.
+
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::{ser, Deserialize, Serialize};
+
+use super::SaveStateError;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+pub fn to_bytes<T: Serialize + ?Sized>(
+    value: &T,
+    output: &mut Vec<u8>,
+) -> Result<(), SaveStateError> {
+    value.serialize(&mut BinarySerializer { output })
+}
+
+pub fn from_bytes<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<T, SaveStateError> {
+    let mut deserializer = BinaryDeserializer { input };
+    let value = T::deserialize(&mut deserializer)?;
+    if !deserializer.input.is_empty() {
+        return Err(SaveStateError::Corrupted);
+    }
+    Ok(value)
+}
+
+struct BinarySerializer<'a> {
+    output: &'a mut Vec<u8>,
+}
+
+impl BinarySerializer<'_> {
+    fn write_len(&mut self, len: usize) -> Result<(), SaveStateError> {
+        let len = u32::try_from(len).map_err(|_| SaveStateError::Corrupted)?;
+        self.output.extend_from_slice(&len.to_le_bytes());
+        Ok(())
+    }
+}
+
+impl ser::Serializer for &mut BinarySerializer<'_> {
+    type Ok = ();
+    type Error = SaveStateError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), SaveStateError> {
+        self.output.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SaveStateError> {
+        self.output.push(v);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SaveStateError> {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SaveStateError> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SaveStateError> {
+        self.write_len(v.len())?;
+        self.output.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), SaveStateError> {
+        self.output.push(0);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SaveStateError> {
+        self.output.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), SaveStateError> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SaveStateError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), SaveStateError> {
+        self.output.extend_from_slice(&variant_index.to_le_bytes());
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, SaveStateError> {
+        self.write_len(len.ok_or(SaveStateError::Corrupted)?)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, SaveStateError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self, SaveStateError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, SaveStateError> {
+        self.output.extend_from_slice(&variant_index.to_le_bytes());
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, SaveStateError> {
+        self.write_len(len.ok_or(SaveStateError::Corrupted)?)?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, SaveStateError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, SaveStateError> {
+        self.output.extend_from_slice(&variant_index.to_le_bytes());
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut BinarySerializer<'_> {
+    type Ok = ();
+    type Error = SaveStateError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SaveStateError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut BinarySerializer<'_> {
+    type Ok = ();
+    type Error = SaveStateError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SaveStateError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut BinarySerializer<'_> {
+    type Ok = ();
+    type Error = SaveStateError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SaveStateError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut BinarySerializer<'_> {
+    type Ok = ();
+    type Error = SaveStateError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SaveStateError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut BinarySerializer<'_> {
+    type Ok = ();
+    type Error = SaveStateError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SaveStateError> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SaveStateError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut BinarySerializer<'_> {
+    type Ok = ();
+    type Error = SaveStateError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SaveStateError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut BinarySerializer<'_> {
+    type Ok = ();
+    type Error = SaveStateError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SaveStateError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+}
+
+struct BinaryDeserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> BinaryDeserializer<'de> {
+    fn take(&mut self, len: usize) -> Result<&'de [u8], SaveStateError> {
+        if self.input.len() < len {
+            return Err(SaveStateError::UnexpectedEnd);
+        }
+        let (head, tail) = self.input.split_at(len);
+        self.input = tail;
+        Ok(head)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn read_len(&mut self) -> Result<usize, SaveStateError> {
+        Ok(u32::from_le_bytes(self.read_array()?) as usize)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut BinaryDeserializer<'de> {
+    type Error = SaveStateError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SaveStateError> {
+        Err(SaveStateError::Corrupted)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        match self.read_array::<1>()?[0] {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            _ => Err(SaveStateError::Corrupted),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_i8(i8::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_i16(i16::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_i32(i32::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_i64(i64::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_i128(i128::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_u8(self.read_array::<1>()?[0])
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_u16(u16::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_u32(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_u64(u64::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_u128(u128::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_f32(f32::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_f64(f64::from_le_bytes(self.read_array()?))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        let value = u32::from_le_bytes(self.read_array()?);
+        visitor.visit_char(char::from_u32(value).ok_or(SaveStateError::Corrupted)?)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        let len = self.read_len()?;
+        let bytes = self.take(len)?;
+        visitor
+            .visit_borrowed_str(core::str::from_utf8(bytes).map_err(|_| SaveStateError::Corrupted)?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        let len = self.read_len()?;
+        visitor.visit_borrowed_bytes(self.take(len)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        match self.read_array::<1>()?[0] {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            _ => Err(SaveStateError::Corrupted),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        let remaining = self.read_len()?;
+        visitor.visit_seq(Access {
+            deserializer: self,
+            remaining,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        visitor.visit_seq(Access {
+            deserializer: self,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SaveStateError> {
+        let remaining = self.read_len()?;
+        visitor.visit_map(Access {
+            deserializer: self,
+            remaining,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        Err(SaveStateError::Corrupted)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        Err(SaveStateError::Corrupted)
+    }
+}
+
+struct Access<'a, 'de> {
+    deserializer: &'a mut BinaryDeserializer<'de>,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Access<'_, 'de> {
+    type Error = SaveStateError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SaveStateError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Access<'_, 'de> {
+    type Error = SaveStateError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SaveStateError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, SaveStateError> {
+        seed.deserialize(&mut *self.deserializer)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut BinaryDeserializer<'de> {
+    type Error = SaveStateError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), SaveStateError> {
+        let index = u32::from_le_bytes(self.read_array()?);
+        let deserializer: de::value::U32Deserializer<SaveStateError> = index.into_deserializer();
+        let value = seed.deserialize(deserializer)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut BinaryDeserializer<'de> {
+    type Error = SaveStateError;
+
+    fn unit_variant(self) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SaveStateError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SaveStateError> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Empty,
+        Newtype(u16),
+        Tuple(u8, i8),
+        Struct { width: u32, height: i64 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Unit;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Everything {
+        flag: bool,
+        small: i16,
+        big: u64,
+        signed: i32,
+        real: f32,
+        double: f64,
+        character: char,
+        text: String,
+        bytes: Vec<u8>,
+        array: [u16; 3],
+        tuple: (u8, bool),
+        optional: Option<u32>,
+        missing: Option<u32>,
+        shapes: Vec<Shape>,
+        unit: Unit,
+        nothing: (),
+    }
+
+    fn everything() -> Everything {
+        Everything {
+            flag: true,
+            small: -2,
+            big: u64::MAX - 1,
+            signed: i32::MIN,
+            real: 1.5,
+            double: -0.25,
+            character: 'é',
+            text: String::from("GBST"),
+            bytes: vec![0, 0xFF, 0x7F],
+            array: [1, 0x100, 0xFFFF],
+            tuple: (9, false),
+            optional: Some(7),
+            missing: None,
+            shapes: vec![
+                Shape::Empty,
+                Shape::Newtype(0x1234),
+                Shape::Tuple(1, -1),
+                Shape::Struct {
+                    width: 160,
+                    height: -144,
+                },
+            ],
+            unit: Unit,
+            nothing: (),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut data = Vec::new();
+        to_bytes(&everything(), &mut data).unwrap();
+        assert_eq!(from_bytes::<Everything>(&data).unwrap(), everything());
+    }
+
+    #[test]
+    fn fixed_width_little_endian() {
+        let mut data = Vec::new();
+        to_bytes(&(0x1234u16, true, Shape::Newtype(0xABCD)), &mut data).unwrap();
+        assert_eq!(data, [0x34, 0x12, 1, 1, 0, 0, 0, 0xCD, 0xAB]);
+
+        data.clear();
+        to_bytes(&vec![5u8, 6], &mut data).unwrap();
+        assert_eq!(data, [2, 0, 0, 0, 5, 6]);
+    }
+
+    #[test]
+    fn truncated_and_trailing_data() {
+        let mut data = Vec::new();
+        to_bytes(&everything(), &mut data).unwrap();
+        assert_eq!(
+            from_bytes::<Everything>(&data[..data.len() - 1]),
+            Err(SaveStateError::UnexpectedEnd)
+        );
+        data.push(0);
+        assert_eq!(
+            from_bytes::<Everything>(&data),
+            Err(SaveStateError::Corrupted)
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!(from_bytes::<bool>(&[2]).is_err());
+        assert!(from_bytes::<Shape>(&[4, 0, 0, 0]).is_err());
+        assert!(from_bytes::<Vec<u8>>(&u32::MAX.to_le_bytes()).is_err());
+    }
+}
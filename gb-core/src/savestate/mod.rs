@@ -0,0 +1,518 @@
+//! Versioned binary container for [`GameBoyState`].
+//!
+//! A state starts with a fixed header: the `GBST` magic, the format version, a flags byte,
+//! the ROM title padded to 16 bytes and the ROM header and global checksums. A chunk count
+//! follows, then every section of the state as a chunk made of a four byte id, a `u32`
+//! length and the payload encoded with [`binary`]. When the compressed flag is set the
+//! payloads are run length encoded with [`rle`].
+//!
+//! Readers skip chunks they do not know, and states written by an older version go through
+//! [`MIGRATIONS`] before being decoded.
+
+pub mod binary;
+pub mod rle;
+
+use crate::gameboy::GameBoyState;
+use crate::hardware::header::CartridgeHeader;
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+pub const MAGIC: [u8; 4] = *b"GBST";
+pub const VERSION: u16 = 1;
+
+/// Largest chunk payload accepted, compressed or not. The biggest chunk is the cartridge
+/// with at most 128KB of RAM, so anything above comes from a corrupted file.
+pub const MAX_CHUNK_LENGTH: usize = 0x100000;
+
+const FLAG_COMPRESSED: u8 = 0x01;
+const TITLE_LENGTH: usize = 16;
+
+pub const CPU_CHUNK: [u8; 4] = *b"CPU ";
+pub const PPU_CHUNK: [u8; 4] = *b"PPU ";
+pub const HARDWARE_CHUNK: [u8; 4] = *b"HW  ";
+pub const CARTRIDGE_CHUNK: [u8; 4] = *b"CART";
+pub const STEP_CHUNK: [u8; 4] = *b"STEP";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveStateError {
+    /// The underlying writer or reader failed.
+    Io,
+    /// The data does not start with the save state magic.
+    InvalidMagic,
+    /// The state was written by a newer version of the format.
+    UnsupportedVersion(u16),
+    /// The state belongs to a different ROM.
+    RomMismatch,
+    /// A chunk required to rebuild the state is missing.
+    MissingChunk([u8; 4]),
+    /// The data ended in the middle of a value.
+    UnexpectedEnd,
+    /// A value could not be decoded.
+    Corrupted,
+    /// Error reported by a serde implementation.
+    Custom(String),
+}
+
+impl core::fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SaveStateError::Io => write!(f, "could not access the save state"),
+            SaveStateError::InvalidMagic => write!(f, "not a save state"),
+            SaveStateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version: {}", version)
+            }
+            SaveStateError::RomMismatch => write!(f, "save state belongs to a different ROM"),
+            SaveStateError::MissingChunk(id) => {
+                write!(
+                    f,
+                    "missing save state chunk: {}",
+                    String::from_utf8_lossy(id)
+                )
+            }
+            SaveStateError::UnexpectedEnd => write!(f, "truncated save state"),
+            SaveStateError::Corrupted => write!(f, "corrupted save state"),
+            SaveStateError::Custom(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl serde::de::StdError for SaveStateError {}
+
+impl serde::ser::Error for SaveStateError {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        SaveStateError::Custom(msg.to_string())
+    }
+}
+
+impl serde::de::Error for SaveStateError {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        SaveStateError::Custom(msg.to_string())
+    }
+}
+
+/// Destination of an encoded state, implemented for every `std::io::Write` and for `Vec<u8>`
+/// without `std`.
+pub trait StateWriter {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), SaveStateError>;
+}
+
+/// Source of an encoded state, implemented for every `std::io::Read` and for `&[u8]` without
+/// `std`.
+pub trait StateReader {
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), SaveStateError>;
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> StateWriter for W {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), SaveStateError> {
+        self.write_all(bytes).map_err(|_| SaveStateError::Io)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> StateReader for R {
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), SaveStateError> {
+        self.read_exact(buffer).map_err(|err| match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => SaveStateError::UnexpectedEnd,
+            _ => SaveStateError::Io,
+        })
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl StateWriter for Vec<u8> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), SaveStateError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl StateReader for &[u8] {
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), SaveStateError> {
+        if self.len() < buffer.len() {
+            return Err(SaveStateError::UnexpectedEnd);
+        }
+        let (head, tail) = self.split_at(buffer.len());
+        buffer.copy_from_slice(head);
+        *self = tail;
+        Ok(())
+    }
+}
+
+/// One section of a state with its payload already decompressed.
+pub struct Chunk {
+    pub id: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// Upgrades the chunks of an older state in place, `MIGRATIONS[n]` turns version `n + 1`
+/// into version `n + 2`.
+pub type Migration = fn(&mut Vec<Chunk>) -> Result<(), SaveStateError>;
+
+/// Payloads are the state fields in declaration order, reordering or adding a field needs a
+/// new version and a migration here.
+pub const MIGRATIONS: [Migration; VERSION as usize - 1] = [];
+
+/// ROM identity stored in the header so states are not loaded into another game.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SaveStateInfo {
+    pub version: u16,
+    pub title: [u8; TITLE_LENGTH],
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl SaveStateInfo {
+    pub fn from_header(header: &CartridgeHeader) -> SaveStateInfo {
+        let mut title = [0; TITLE_LENGTH];
+        let length = header.title.len().min(TITLE_LENGTH);
+        title[..length].copy_from_slice(&header.title.as_bytes()[..length]);
+        SaveStateInfo {
+            version: VERSION,
+            title,
+            header_checksum: header.header_checksum,
+            global_checksum: header.global_checksum,
+        }
+    }
+
+    pub fn matches(&self, header: &CartridgeHeader) -> bool {
+        let expected = SaveStateInfo::from_header(header);
+        self.title == expected.title
+            && self.header_checksum == expected.header_checksum
+            && self.global_checksum == expected.global_checksum
+    }
+}
+
+pub fn write_state<W: StateWriter>(
+    writer: &mut W,
+    state: &GameBoyState,
+    header: &CartridgeHeader,
+    compress: bool,
+) -> Result<(), SaveStateError> {
+    let info = SaveStateInfo::from_header(header);
+    let chunks = [
+        (CPU_CHUNK, encode(&state.cpu_state)?),
+        (PPU_CHUNK, encode(&state.ppu_state)?),
+        (HARDWARE_CHUNK, encode(&state.hard_ware_state)?),
+        (CARTRIDGE_CHUNK, encode(&state.cartridge_state)?),
+        (STEP_CHUNK, encode(&state.state)?),
+    ];
+
+    writer.write_bytes(&MAGIC)?;
+    writer.write_bytes(&VERSION.to_le_bytes())?;
+    writer.write_bytes(&[if compress { FLAG_COMPRESSED } else { 0 }])?;
+    writer.write_bytes(&info.title)?;
+    writer.write_bytes(&[info.header_checksum])?;
+    writer.write_bytes(&info.global_checksum.to_le_bytes())?;
+    writer.write_bytes(&(chunks.len() as u16).to_le_bytes())?;
+
+    let mut compressed = Vec::new();
+    for (id, data) in chunks.iter() {
+        let payload = if compress {
+            compressed.clear();
+            rle::compress(data, &mut compressed);
+            &compressed
+        } else {
+            data
+        };
+        writer.write_bytes(id)?;
+        writer.write_bytes(&(payload.len() as u32).to_le_bytes())?;
+        writer.write_bytes(payload)?;
+    }
+    Ok(())
+}
+
+/// Reads only the header, frontends use it to label state slots.
+pub fn read_info<R: StateReader>(reader: &mut R) -> Result<SaveStateInfo, SaveStateError> {
+    read_header(reader).map(|(info, _)| info)
+}
+
+pub fn read_state<R: StateReader>(
+    reader: &mut R,
+    header: &CartridgeHeader,
+) -> Result<GameBoyState, SaveStateError> {
+    let (info, flags) = read_header(reader)?;
+    if !info.matches(header) {
+        return Err(SaveStateError::RomMismatch);
+    }
+
+    let count = u16::from_le_bytes(read_array(reader)?);
+    let mut chunks = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let id = read_array(reader)?;
+        let length = u32::from_le_bytes(read_array(reader)?) as usize;
+        if length > MAX_CHUNK_LENGTH {
+            return Err(SaveStateError::Corrupted);
+        }
+        let mut payload = vec![0; length];
+        reader.read_bytes(&mut payload)?;
+        let data = if flags & FLAG_COMPRESSED != 0 {
+            let mut data = Vec::new();
+            rle::decompress(&payload, &mut data, MAX_CHUNK_LENGTH)?;
+            data
+        } else {
+            payload
+        };
+        chunks.push(Chunk { id, data });
+    }
+
+    for migration in MIGRATIONS.iter().skip(info.version as usize - 1) {
+        migration(&mut chunks)?;
+    }
+
+    Ok(GameBoyState {
+        cpu_state: decode(&chunks, CPU_CHUNK)?,
+        ppu_state: decode(&chunks, PPU_CHUNK)?,
+        hard_ware_state: decode(&chunks, HARDWARE_CHUNK)?,
+        cartridge_state: decode(&chunks, CARTRIDGE_CHUNK)?,
+        state: decode(&chunks, STEP_CHUNK)?,
+    })
+}
+
+fn read_header<R: StateReader>(reader: &mut R) -> Result<(SaveStateInfo, u8), SaveStateError> {
+    let magic: [u8; 4] = read_array(reader)?;
+    if magic != MAGIC {
+        return Err(SaveStateError::InvalidMagic);
+    }
+    let version = u16::from_le_bytes(read_array(reader)?);
+    if version == 0 || version > VERSION {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+    let [flags] = read_array(reader)?;
+    let info = SaveStateInfo {
+        version,
+        title: read_array(reader)?,
+        header_checksum: read_array::<_, 1>(reader)?[0],
+        global_checksum: u16::from_le_bytes(read_array(reader)?),
+    };
+    Ok((info, flags))
+}
+
+fn read_array<R: StateReader, const N: usize>(reader: &mut R) -> Result<[u8; N], SaveStateError> {
+    let mut array = [0; N];
+    reader.read_bytes(&mut array)?;
+    Ok(array)
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, SaveStateError> {
+    let mut data = Vec::new();
+    binary::to_bytes(value, &mut data)?;
+    Ok(data)
+}
+
+fn decode<'de, T: serde::Deserialize<'de>>(
+    chunks: &'de [Chunk],
+    id: [u8; 4],
+) -> Result<T, SaveStateError> {
+    let chunk = chunks
+        .iter()
+        .find(|chunk| chunk.id == id)
+        .ok_or(SaveStateError::MissingChunk(id))?;
+    binary::from_bytes(&chunk.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::GameBoy;
+    use crate::hardware::boot_rom::{Bootrom, BootromData};
+    use crate::test_util::{rom, NullPlayer, NullScreen};
+
+    /// A running MBC3 game with its header.
+    fn running() -> (GameBoy<'static, NullScreen>, CartridgeHeader) {
+        // LD A,$0A; LD ($0000),A; INC A; LD ($A000),A; JR -6
+        let rom = rom(
+            0x10,
+            0x01,
+            0x03,
+            &[
+                0x3E, 0x0A, 0xEA, 0x00, 0x00, 0x3C, 0xEA, 0x00, 0xA0, 0x18, 0xFA,
+            ],
+        );
+        let header = rom.header.clone();
+        let mut gameboy = GameBoy::create(
+            NullScreen,
+            rom.into_cartridge(),
+            Bootrom::new(None),
+            Box::new(NullPlayer),
+        );
+        for _ in 0..20000 {
+            gameboy.tick();
+        }
+        (gameboy, header)
+    }
+
+    fn write(state: &GameBoyState, header: &CartridgeHeader, compress: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_state(&mut data, state, header, compress).unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trip() {
+        let (gameboy, header) = running();
+        for compress in [false, true] {
+            let data = write(&gameboy.create_state(), &header, compress);
+            let state = read_state(&mut data.as_slice(), &header).unwrap();
+            assert_eq!(write(&state, &header, compress), data);
+        }
+    }
+
+    #[test]
+    fn loaded_state_runs_the_same() {
+        let (mut gameboy, header) = running();
+        let data = write(&gameboy.create_state(), &header, true);
+        let (mut other, _) = running();
+        for _ in 0..1000 {
+            other.tick();
+        }
+        other.load_state(read_state(&mut data.as_slice(), &header).unwrap());
+        for _ in 0..5000 {
+            gameboy.tick();
+            other.tick();
+        }
+        assert_eq!(
+            write(&gameboy.create_state(), &header, false),
+            write(&other.create_state(), &header, false)
+        );
+    }
+
+    #[test]
+    fn info_and_rom_check() {
+        let (gameboy, header) = running();
+        let data = write(&gameboy.create_state(), &header, true);
+        let info = read_info(&mut data.as_slice()).unwrap();
+        assert_eq!(info.version, VERSION);
+        assert_eq!(&info.title[..4], b"TEST");
+        assert!(info.matches(&header));
+
+        let mut other = header.clone();
+        other.global_checksum ^= 1;
+        assert_eq!(
+            read_state(&mut data.as_slice(), &other).err(),
+            Some(SaveStateError::RomMismatch)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let (gameboy, header) = running();
+        let data = write(&gameboy.create_state(), &header, true);
+
+        let mut magic = data.clone();
+        magic[0] = b'X';
+        assert_eq!(
+            read_info(&mut magic.as_slice()).err(),
+            Some(SaveStateError::InvalidMagic)
+        );
+
+        let mut version = data.clone();
+        version[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            read_info(&mut version.as_slice()).err(),
+            Some(SaveStateError::UnsupportedVersion(VERSION + 1))
+        );
+
+        assert_eq!(
+            read_state(&mut &data[..data.len() - 1], &header).err(),
+            Some(SaveStateError::UnexpectedEnd)
+        );
+    }
+
+    /// Header for `header` followed by the chunk count.
+    fn container(header: &CartridgeHeader, version: u16, count: u16) -> Vec<u8> {
+        let info = SaveStateInfo::from_header(header);
+        let mut data = Vec::new();
+        data.extend_from_slice(&MAGIC);
+        data.extend_from_slice(&version.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(&info.title);
+        data.push(info.header_checksum);
+        data.extend_from_slice(&info.global_checksum.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn rejects_oversized_chunks() {
+        let (_, header) = running();
+        let mut data = container(&header, VERSION, 1);
+        data.extend_from_slice(&CPU_CHUNK);
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            read_state(&mut data.as_slice(), &header).err(),
+            Some(SaveStateError::Corrupted)
+        );
+    }
+
+    #[test]
+    fn skips_unknown_chunks() {
+        let (gameboy, header) = running();
+        let state = gameboy.create_state();
+        let chunks = [
+            (*b"NEW ", vec![1, 2, 3]),
+            (CPU_CHUNK, encode(&state.cpu_state).unwrap()),
+            (PPU_CHUNK, encode(&state.ppu_state).unwrap()),
+            (HARDWARE_CHUNK, encode(&state.hard_ware_state).unwrap()),
+            (CARTRIDGE_CHUNK, encode(&state.cartridge_state).unwrap()),
+            (STEP_CHUNK, encode(&state.state).unwrap()),
+        ];
+        let mut data = container(&header, VERSION, chunks.len() as u16);
+        for (id, payload) in chunks.iter() {
+            data.extend_from_slice(id);
+            data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            data.extend_from_slice(payload);
+        }
+        let read = read_state(&mut data.as_slice(), &header).unwrap();
+        assert_eq!(write(&read, &header, false), write(&state, &header, false));
+    }
+
+    #[test]
+    fn chunks_follow_the_field_order() {
+        let (gameboy, _) = running();
+        let mut state = gameboy.create_state();
+        let registers = &mut state.cpu_state.registers;
+        (registers.a, registers.b, registers.c, registers.d) = (1, 2, 3, 4);
+        (registers.e, registers.h, registers.l) = (5, 6, 7);
+        registers.flags.z = true;
+        registers.flags.c = true;
+        (registers.sp, registers.pc) = (0xFFFE, 0x0150);
+        assert_eq!(
+            encode(&state.cpu_state).unwrap(),
+            [1, 2, 3, 4, 5, 6, 7, 1, 0, 0, 1, 0xFE, 0xFF, 0x50, 0x01]
+        );
+    }
+
+    #[test]
+    fn restores_the_boot_rom_mapping() {
+        let (gameboy, header) = running();
+        let booted = write(&gameboy.create_state(), &header, true);
+
+        let rom = rom(0x10, 0x01, 0x03, &[]);
+        let mut booting = GameBoy::create(
+            NullScreen,
+            rom.into_cartridge(),
+            Bootrom::new(Some(BootromData::from_bytes(&[0xFF; 0x100]))),
+            Box::new(NullPlayer),
+        );
+        let mid_boot = write(&booting.create_state(), &header, true);
+        assert_eq!(booting.peek(0x0000), 0xFF);
+
+        booting.load_state(read_state(&mut booted.as_slice(), &header).unwrap());
+        assert_eq!(booting.peek(0x0000), 0x00);
+
+        booting.load_state(read_state(&mut mid_boot.as_slice(), &header).unwrap());
+        assert_eq!(booting.peek(0x0000), 0xFF);
+
+        // Nothing to map without boot ROM data
+        let (mut other, _) = running();
+        other.load_state(read_state(&mut mid_boot.as_slice(), &header).unwrap());
+        assert_eq!(other.peek(0x0000), 0x00);
+    }
+}
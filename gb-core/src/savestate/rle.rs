@@ -0,0 +1,144 @@
+//! Run length encoding tuned for emulator memory, which is mostly long runs of zeros.
+//!
+//! Each packet starts with a control byte: values below 0x80 are followed by `n + 1`
+//! literal bytes, values from 0x80 repeat the next byte `n - 126` times.
+
+use super::SaveStateError;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+const MAX_LITERAL: usize = 0x80;
+const MIN_RUN: usize = 3;
+const MAX_RUN: usize = 0xFF - 126;
+
+fn run_length(input: &[u8], start: usize) -> usize {
+    let value = input[start];
+    input[start..]
+        .iter()
+        .take(MAX_RUN)
+        .take_while(|byte| **byte == value)
+        .count()
+}
+
+pub fn compress(input: &[u8], output: &mut Vec<u8>) {
+    let mut index = 0;
+    while index < input.len() {
+        let run = run_length(input, index);
+        if run >= MIN_RUN {
+            output.push((run + 126) as u8);
+            output.push(input[index]);
+            index += run;
+            continue;
+        }
+        let start = index;
+        while index < input.len()
+            && index - start < MAX_LITERAL
+            && run_length(input, index) < MIN_RUN
+        {
+            index += 1;
+        }
+        output.push((index - start - 1) as u8);
+        output.extend_from_slice(&input[start..index]);
+    }
+}
+
+/// Fails with [`SaveStateError::Corrupted`] instead of growing `output` past `limit` bytes.
+pub fn decompress(input: &[u8], output: &mut Vec<u8>, limit: usize) -> Result<(), SaveStateError> {
+    let mut index = 0;
+    while index < input.len() {
+        let control = input[index] as usize;
+        index += 1;
+        if control < MAX_LITERAL {
+            let literal = input
+                .get(index..index + control + 1)
+                .ok_or(SaveStateError::UnexpectedEnd)?;
+            if output.len() + literal.len() > limit {
+                return Err(SaveStateError::Corrupted);
+            }
+            output.extend_from_slice(literal);
+            index += control + 1;
+        } else {
+            let value = *input.get(index).ok_or(SaveStateError::UnexpectedEnd)?;
+            if output.len() + control - 126 > limit {
+                return Err(SaveStateError::Corrupted);
+            }
+            output.resize(output.len() + control - 126, value);
+            index += 1;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: &[u8]) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        compress(input, &mut compressed);
+        let mut output = Vec::new();
+        decompress(&compressed, &mut output, input.len()).unwrap();
+        assert_eq!(output, input);
+        compressed
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(round_trip(&[]).is_empty());
+    }
+
+    #[test]
+    fn short_repeats_stay_literal() {
+        assert_eq!(round_trip(&[7]), [0x00, 7]);
+        assert_eq!(round_trip(&[7, 7]), [0x01, 7, 7]);
+        assert_eq!(round_trip(&[7, 7, 7]), [0x80 + 1, 7]);
+    }
+
+    #[test]
+    fn runs_split_at_the_longest_packet() {
+        assert_eq!(round_trip(&[0; MAX_RUN]), [0xFF, 0]);
+        assert_eq!(round_trip(&[0; MAX_RUN + 1]), [0xFF, 0, 0x00, 0]);
+        assert_eq!(round_trip(&[0; MAX_RUN + 3]), [0xFF, 0, 0x81, 0]);
+    }
+
+    #[test]
+    fn literals_split_at_the_longest_packet() {
+        let input: Vec<u8> = (0..=255).collect();
+        let compressed = round_trip(&input);
+        assert_eq!(compressed.len(), input.len() + 2);
+        assert_eq!(compressed[0], 0x7F);
+        assert_eq!(compressed[MAX_LITERAL + 1], 0x7F);
+    }
+
+    #[test]
+    fn literal_stops_before_a_run() {
+        assert_eq!(round_trip(&[1, 2, 3, 3, 3]), [0x01, 1, 2, 0x81, 3]);
+    }
+
+    #[test]
+    fn output_is_limited() {
+        let mut output = Vec::new();
+        assert_eq!(
+            decompress(&[0xFF, 0], &mut output, MAX_RUN - 1),
+            Err(SaveStateError::Corrupted)
+        );
+        assert_eq!(
+            decompress(&[0x02, 1, 2, 3], &mut output, 2),
+            Err(SaveStateError::Corrupted)
+        );
+    }
+
+    #[test]
+    fn truncated_packets() {
+        let mut output = Vec::new();
+        assert_eq!(
+            decompress(&[0x80], &mut output, 16),
+            Err(SaveStateError::UnexpectedEnd)
+        );
+        assert_eq!(
+            decompress(&[0x03, 1, 2], &mut output, 16),
+            Err(SaveStateError::UnexpectedEnd)
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! Cheat search over the RAM a game keeps its variables in.
+//!
+//! A search starts with a snapshot of every candidate address, each filter then compares
+//! the current values against the previous pass and drops the addresses that do not match.
+//! Memory is read through [`GameBoy::peek`] so searching never disturbs the emulation.
+//! Cartridge RAM is read through whatever bank is currently mapped.
+
+use crate::gameboy::GameBoy;
+use crate::hardware::Screen;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchRegion {
+    CartridgeRam,
+    WorkRam,
+    HighRam,
+}
+
+impl SearchRegion {
+    pub const ALL: [SearchRegion; 3] = [
+        SearchRegion::CartridgeRam,
+        SearchRegion::WorkRam,
+        SearchRegion::HighRam,
+    ];
+
+    /// First and last address of the region.
+    pub fn range(&self) -> (u16, u16) {
+        match self {
+            SearchRegion::CartridgeRam => (0xA000, 0xBFFF),
+            SearchRegion::WorkRam => (0xC000, 0xDFFF),
+            SearchRegion::HighRam => (0xFF80, 0xFFFE),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueSize {
+    Byte,
+    /// Two bytes, little endian like the CPU stores them.
+    Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueEncoding {
+    Binary,
+    /// Two decimal digits per byte, as used by most score counters.
+    Bcd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFilter {
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+    Equal(u16),
+}
+
+impl SearchFilter {
+    fn matches(&self, previous: u16, current: u16) -> bool {
+        match self {
+            SearchFilter::Unchanged => current == previous,
+            SearchFilter::Changed => current != previous,
+            SearchFilter::Increased => current > previous,
+            SearchFilter::Decreased => current < previous,
+            SearchFilter::Equal(value) => current == *value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    pub address: u16,
+    pub value: u16,
+    pub previous: u16,
+}
+
+pub struct MemorySearch {
+    size: ValueSize,
+    encoding: ValueEncoding,
+    regions: Vec<SearchRegion>,
+    results: Vec<SearchResult>,
+}
+
+impl MemorySearch {
+    pub fn new(size: ValueSize, encoding: ValueEncoding) -> Self {
+        Self::with_regions(size, encoding, &SearchRegion::ALL)
+    }
+
+    pub fn with_regions(
+        size: ValueSize,
+        encoding: ValueEncoding,
+        regions: &[SearchRegion],
+    ) -> Self {
+        Self {
+            size,
+            encoding,
+            regions: regions.to_vec(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Snapshots every address of the searched regions, discarding earlier results.
+    pub fn start<S: Screen>(&mut self, gameboy: &GameBoy<S>) {
+        self.results.clear();
+        let width = match self.size {
+            ValueSize::Byte => 0,
+            ValueSize::Word => 1,
+        };
+        for region in self.regions.iter() {
+            let (start, end) = region.range();
+            // Words never straddle the end of a region
+            for address in start..=end - width {
+                if let Some(value) = read_value(gameboy, address, self.size, self.encoding) {
+                    self.results.push(SearchResult {
+                        address,
+                        value,
+                        previous: value,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Keeps the addresses whose value moved from the previous pass as `filter` asks.
+    pub fn filter<S: Screen>(&mut self, gameboy: &GameBoy<S>, filter: SearchFilter) {
+        let size = self.size;
+        let encoding = self.encoding;
+        self.results.retain_mut(|result| {
+            match read_value(gameboy, result.address, size, encoding) {
+                Some(value) if filter.matches(result.value, value) => {
+                    result.previous = result.value;
+                    result.value = value;
+                    true
+                }
+                _ => false,
+            }
+        });
+    }
+
+    pub fn results(&self) -> &[SearchResult] {
+        &self.results
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
+
+/// `None` when the bytes are not valid BCD.
+fn read_value<S: Screen>(
+    gameboy: &GameBoy<S>,
+    address: u16,
+    size: ValueSize,
+    encoding: ValueEncoding,
+) -> Option<u16> {
+    let bytes = match size {
+        ValueSize::Byte => [gameboy.peek(address), 0],
+        ValueSize::Word => [gameboy.peek(address), gameboy.peek(address + 1)],
+    };
+    match encoding {
+        ValueEncoding::Binary => Some(u16::from_le_bytes(bytes)),
+        ValueEncoding::Bcd => Some(decode_bcd(bytes[1])? * 100 + decode_bcd(bytes[0])?),
+    }
+}
+
+fn decode_bcd(value: u8) -> Option<u16> {
+    let tens = value >> 4;
+    let ones = value & 0x0F;
+    if tens > 9 || ones > 9 {
+        return None;
+    }
+    Some((tens * 10 + ones) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::gameboy;
+
+    fn addresses(search: &MemorySearch) -> Vec<u16> {
+        search
+            .results()
+            .iter()
+            .map(|result| result.address)
+            .collect()
+    }
+
+    #[test]
+    fn filters_narrow_the_candidates() {
+        let mut gameboy = gameboy(&[]);
+        let regions = [SearchRegion::WorkRam, SearchRegion::HighRam];
+        let mut search =
+            MemorySearch::with_regions(ValueSize::Byte, ValueEncoding::Binary, &regions);
+        gameboy.poke(0xC100, 5);
+        gameboy.poke(0xC200, 5);
+        gameboy.poke(0xFF90, 5);
+        search.start(&gameboy);
+        assert_eq!(search.len(), 0x2000 + 0x7F);
+
+        search.filter(&gameboy, SearchFilter::Equal(5));
+        assert_eq!(addresses(&search), [0xC100, 0xC200, 0xFF90]);
+
+        gameboy.poke(0xC100, 6);
+        gameboy.poke(0xFF90, 4);
+        search.filter(&gameboy, SearchFilter::Changed);
+        assert_eq!(addresses(&search), [0xC100, 0xFF90]);
+
+        gameboy.poke(0xC100, 7);
+        gameboy.poke(0xFF90, 3);
+        search.filter(&gameboy, SearchFilter::Decreased);
+        assert_eq!(
+            search.results(),
+            [SearchResult {
+                address: 0xFF90,
+                value: 3,
+                previous: 4
+            }]
+        );
+
+        search.filter(&gameboy, SearchFilter::Increased);
+        assert!(search.is_empty());
+    }
+
+    #[test]
+    fn unchanged_keeps_everything_that_did_not_move() {
+        let mut gameboy = gameboy(&[]);
+        let mut search = MemorySearch::with_regions(
+            ValueSize::Byte,
+            ValueEncoding::Binary,
+            &[SearchRegion::HighRam],
+        );
+        search.start(&gameboy);
+        gameboy.poke(0xFF80, 1);
+        gameboy.poke(0xFFFE, 1);
+        search.filter(&gameboy, SearchFilter::Unchanged);
+        assert_eq!(search.len(), 0x7F - 2);
+        assert_eq!(addresses(&search)[0], 0xFF81);
+        assert_eq!(addresses(&search).last(), Some(&0xFFFD));
+    }
+
+    #[test]
+    fn reads_little_endian_words() {
+        let mut gameboy = gameboy(&[]);
+        let mut search = MemorySearch::with_regions(
+            ValueSize::Word,
+            ValueEncoding::Binary,
+            &[SearchRegion::HighRam],
+        );
+        gameboy.poke(0xFF90, 0x34);
+        gameboy.poke(0xFF91, 0x12);
+        search.start(&gameboy);
+        // Words never start on the last byte of the region
+        assert_eq!(search.len(), 0x7E);
+
+        search.filter(&gameboy, SearchFilter::Equal(0x1234));
+        assert_eq!(addresses(&search), [0xFF90]);
+        gameboy.poke(0xFF90, 0x35);
+        search.filter(&gameboy, SearchFilter::Increased);
+        assert_eq!(search.results()[0].value, 0x1235);
+    }
+
+    #[test]
+    fn decodes_bcd_and_skips_invalid_digits() {
+        let mut gameboy = gameboy(&[]);
+        let mut search = MemorySearch::with_regions(
+            ValueSize::Word,
+            ValueEncoding::Bcd,
+            &[SearchRegion::WorkRam],
+        );
+        // A score of 1299, stored low digits first
+        gameboy.poke(0xC300, 0x99);
+        gameboy.poke(0xC301, 0x12);
+        gameboy.poke(0xC400, 0xAB);
+        search.start(&gameboy);
+        // Both words holding 0xAB are not BCD
+        assert_eq!(search.len(), 0x1FFF - 2);
+        assert!(!addresses(&search).contains(&0xC3FF));
+        assert!(!addresses(&search).contains(&0xC400));
+
+        search.filter(&gameboy, SearchFilter::Equal(1299));
+        assert_eq!(addresses(&search), [0xC300]);
+
+        gameboy.poke(0xC300, 0x00);
+        gameboy.poke(0xC301, 0x13);
+        search.filter(&gameboy, SearchFilter::Increased);
+        assert_eq!(
+            search.results(),
+            [SearchResult {
+                address: 0xC300,
+                value: 1300,
+                previous: 1299
+            }]
+        );
+
+        // Candidates that stop being BCD are dropped
+        gameboy.poke(0xC300, 0x0F);
+        search.filter(&gameboy, SearchFilter::Changed);
+        assert!(search.is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+//! Synthetic cartridges and a headless Game Boy for the unit tests.
+
+use crate::gameboy::GameBoy;
+use crate::hardware::boot_rom::Bootrom;
+use crate::hardware::color_palette::Color;
+use crate::hardware::rom::{Rom, RomManager};
+use crate::hardware::sound::AudioPlayer;
+use crate::hardware::Screen;
+use core::ops::{Index, Range};
+
+/// Where [`rom`] places the program, the entry point jumps there.
+pub const PROGRAM_START: usize = 0x150;
+
+pub struct TestRom {
+    pub data: Vec<u8>,
+    pub clock: u64,
+    /// Banks written through [`RomManager::save`].
+    pub saves: usize,
+}
+
+impl Index<usize> for TestRom {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.data[index]
+    }
+}
+
+impl Index<Range<usize>> for TestRom {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.data[index]
+    }
+}
+
+impl RomManager for TestRom {
+    fn read_from_offset(&self, seek_offset: usize, index: usize, _bank_number: u16) -> u8 {
+        self.data[seek_offset + index]
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn clock(&self) -> u64 {
+        self.clock
+    }
+
+    fn save(&mut self, _game_title: &str, _bank_index: u8, _bank: &[u8]) {
+        self.saves += 1;
+    }
+
+    fn load_to_bank(&mut self, _game_title: &str, _bank_index: u8, _bank: &mut [u8]) {}
+}
+
+/// Builds a cartridge from the header codes at 0x147-0x149. Every switchable bank is
+/// filled with the low byte of its number and `program` runs from [`PROGRAM_START`].
+pub fn rom(cartridge_type: u8, rom_size: u8, ram_size: u8, program: &[u8]) -> Rom<TestRom> {
+    Rom::from_bytes(rom_data(cartridge_type, rom_size, ram_size, program)).unwrap()
+}
+
+/// The bytes behind [`rom`], for tests that break the header before parsing it.
+pub fn rom_data(cartridge_type: u8, rom_size: u8, ram_size: u8, program: &[u8]) -> TestRom {
+    let banks = 2usize << rom_size;
+    let mut data = vec![0; banks * 0x4000];
+    for (bank, chunk) in data.chunks_mut(0x4000).enumerate().skip(1) {
+        chunk.fill(bank as u8);
+    }
+    // JP PROGRAM_START
+    data[0x100..0x104].copy_from_slice(&[0x00, 0xC3, PROGRAM_START as u8, 0x01]);
+    data[0x134..0x138].copy_from_slice(b"TEST");
+    data[0x147] = cartridge_type;
+    data[0x148] = rom_size;
+    data[0x149] = ram_size;
+    data[PROGRAM_START..PROGRAM_START + program.len()].copy_from_slice(program);
+    TestRom {
+        data,
+        clock: 0,
+        saves: 0,
+    }
+}
+
+pub struct NullScreen;
+
+impl Screen for NullScreen {
+    fn turn_on(&mut self) {}
+
+    fn turn_off(&mut self) {}
+
+    fn set_pixel(&mut self, _x: u8, _y: u8, _color: Color) {}
+
+    fn draw(&mut self, _skip_next: bool) {}
+
+    fn frame_rate(&self) -> u8 {
+        60
+    }
+}
+
+pub struct NullPlayer;
+
+impl AudioPlayer for NullPlayer {
+    fn play(&mut self, _output_buffer: &[u16]) {}
+
+    fn samples_rate(&self) -> u32 {
+        44100
+    }
+
+    fn underflowed(&self) -> bool {
+        false
+    }
+}
+
+/// A DMG past the boot ROM with `program` on an MBC3 cartridge with RAM and a clock.
+pub fn gameboy(program: &[u8]) -> GameBoy<'static, NullScreen> {
+    let cartridge = rom(0x10, 0x00, 0x02, program).into_cartridge();
+    GameBoy::create(
+        NullScreen,
+        cartridge,
+        Bootrom::new(None),
+        Box::new(NullPlayer),
+    )
+}
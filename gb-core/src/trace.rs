@@ -0,0 +1,117 @@
+//! Instruction trace in the format of gameboy-doctor.
+//!
+//! While logging is enabled through [`crate::enable_logging`] every instruction the CPU is
+//! about to run is handed to the attached [`TraceSink`] as one [`TraceLine`]. Reference logs
+//! are recorded with LY stuck at 0x90, enable
+//! [`GameBoy::set_doctor_ly`](crate::gameboy::GameBoy::set_doctor_ly) to match them.
+
+use core::fmt;
+
+/// Receives one line per instruction, see [`crate::gameboy::GameBoy::attach_trace`].
+pub trait TraceSink {
+    fn trace(&mut self, line: &TraceLine);
+}
+
+/// CPU state before an instruction runs, displayed as
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceLine {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    /// The four bytes starting at PC.
+    pub pc_mem: [u8; 4],
+}
+
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            self.a,
+            self.f,
+            self.b,
+            self.c,
+            self.d,
+            self.e,
+            self.h,
+            self.l,
+            self.sp,
+            self.pc,
+            self.pc_mem[0],
+            self.pc_mem[1],
+            self.pc_mem[2],
+            self.pc_mem[3]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::gameboy;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Lines(Rc<RefCell<Vec<TraceLine>>>);
+
+    impl TraceSink for Lines {
+        fn trace(&mut self, line: &TraceLine) {
+            self.0.borrow_mut().push(*line);
+        }
+    }
+
+    #[test]
+    fn displays_the_gameboy_doctor_format() {
+        let line = TraceLine {
+            a: 0x01,
+            f: 0xB0,
+            b: 0x00,
+            c: 0x13,
+            d: 0x00,
+            e: 0xD8,
+            h: 0x01,
+            l: 0x4D,
+            sp: 0xFFFE,
+            pc: 0x0100,
+            pc_mem: [0x00, 0xC3, 0x13, 0x02],
+        };
+        assert_eq!(
+            line.to_string(),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02"
+        );
+    }
+
+    #[test]
+    fn traces_instructions_with_ly_stuck() {
+        // LDH A,($44); JR @
+        let mut gameboy = gameboy(&[0xF0, 0x44, 0x18, 0xFE]);
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        gameboy.attach_trace(Box::new(Lines(lines.clone())));
+        gameboy.set_doctor_ly(true);
+        crate::enable_logging();
+        for _ in 0..3 {
+            gameboy.tick();
+        }
+        crate::disable_logging();
+
+        let lines = lines.borrow();
+        assert_eq!(
+            lines[0].to_string(),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01"
+        );
+        assert_eq!(lines[2].pc, 0x0150);
+        assert_eq!(gameboy.cpu.registers.a, 0x90);
+        assert_eq!(gameboy.peek(0xFF44), 0x90);
+
+        gameboy.set_doctor_ly(false);
+        assert_eq!(gameboy.peek(0xFF44), gameboy.cpu.interface.gpu.scanline);
+    }
+}
@@ -1,8 +1,8 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use gb_core::hardware::boot_rom::{Bootrom, BootromData};
 use gb_core::hardware::sound::AudioPlayer;
-use gb_core::{gameboy::GameBoy, gameboy::GameBoyState, hardware::Screen};
-use std::fs::{self, File};
+use gb_core::{gameboy::GameBoy, hardware::Screen};
+use std::fs::File;
 use std::hint::black_box;
 use std::io::Read;
 use std::time::Instant;
@@ -15,14 +15,14 @@ fn fibonacci() {
         .map_err(|_| "Could not read ROM")
         .unwrap();
     let gb_rom = ByteRomManager::new(gb_rom.into_boxed_slice());
-    let gb_rom = gb_core::hardware::rom::Rom::from_bytes(gb_rom);
+    let gb_rom = gb_core::hardware::rom::Rom::from_bytes(gb_rom).unwrap();
 
     let boot_room_stuff = Bootrom::new(Some(BootromData::from_bytes(include_bytes!(
         "C:\\roms\\dmg_boot.bin"
     ))));
 
-    let state = fs::read_to_string("C:\\roms\\pk.state").unwrap();
-    let gb_state = serde_json::from_str::<GameBoyState>(&state).unwrap(); //GameBoyState
+    let mut state = File::open("C:\\roms\\pk.state").unwrap();
+    let gb_state = gb_core::savestate::read_state(&mut state, &gb_rom.header).unwrap();
 
     let cart = gb_rom.into_cartridge();
     let mut gameboy: GameBoy<'_, DummyScreen> = GameBoy::create_from_state(
@@ -105,11 +105,15 @@ impl ByteRomManager {
 }
 
 impl gb_core::hardware::rom::RomManager for ByteRomManager {
-    fn read_from_offset(&self, seek_offset: usize, index: usize, bank_number: u8) -> u8 {
+    fn read_from_offset(&self, seek_offset: usize, index: usize, bank_number: u16) -> u8 {
         let address = seek_offset + index;
         self.data[address]
     }
 
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
     fn clock(&self) -> u64 {
         self.instant.elapsed().as_micros() as u64
         //print!("rr");
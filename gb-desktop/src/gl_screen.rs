@@ -73,6 +73,18 @@ pub fn render(screen: GlScreen, sender: Sender<EmulatorKeyEvent>) {
                         if glutinkey == VirtualKeyCode::F5 {
                             let _ = sender.send(EmulatorKeyEvent::Save);
                         }
+                        if glutinkey == VirtualKeyCode::F8 {
+                            let _ = sender.send(EmulatorKeyEvent::Load);
+                        }
+                        if glutinkey == VirtualKeyCode::F9 {
+                            let _ = sender.send(EmulatorKeyEvent::ToggleTrace);
+                        }
+                        if glutinkey == VirtualKeyCode::Back {
+                            let _ = sender.send(EmulatorKeyEvent::Rewind(true));
+                        }
+                        if let Some(slot) = glium_key_to_slot(glutinkey) {
+                            let _ = sender.send(EmulatorKeyEvent::SelectSlot(slot));
+                        }
                         if let Some(key) = glium_key_to_button(glutinkey) {
                             let _ = sender.send(EmulatorKeyEvent::GbEvent(GbEvents::KeyDown(key)));
                         }
@@ -82,6 +94,9 @@ pub fn render(screen: GlScreen, sender: Sender<EmulatorKeyEvent>) {
                         virtual_keycode: Some(glutinkey),
                         ..
                     } => {
+                        if glutinkey == VirtualKeyCode::Back {
+                            let _ = sender.send(EmulatorKeyEvent::Rewind(false));
+                        }
                         if let Some(key) = glium_key_to_button(glutinkey) {
                             let _ = sender.send(EmulatorKeyEvent::GbEvent(GbEvents::KeyUp(key)));
                         }
@@ -213,3 +228,20 @@ fn glium_key_to_button(key: glium::glutin::event::VirtualKeyCode) -> Option<Butt
         _ => None,
     }
 }
+
+fn glium_key_to_slot(key: glium::glutin::event::VirtualKeyCode) -> Option<u8> {
+    use glium::glutin::event::VirtualKeyCode;
+    match key {
+        VirtualKeyCode::Key0 => Some(0),
+        VirtualKeyCode::Key1 => Some(1),
+        VirtualKeyCode::Key2 => Some(2),
+        VirtualKeyCode::Key3 => Some(3),
+        VirtualKeyCode::Key4 => Some(4),
+        VirtualKeyCode::Key5 => Some(5),
+        VirtualKeyCode::Key6 => Some(6),
+        VirtualKeyCode::Key7 => Some(7),
+        VirtualKeyCode::Key8 => Some(8),
+        VirtualKeyCode::Key9 => Some(9),
+        _ => None,
+    }
+}
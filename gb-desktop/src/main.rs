@@ -2,25 +2,92 @@ mod fb_screen;
 pub mod gl_screen;
 
 use crate::gl_screen::{render, GlScreen};
+use gb_core::debugger::DebugEvent;
 use gb_core::gameboy::{GameBoy, GameBoyState, GbEvents, SCREEN_PIXELS, SCREEN_WIDTH};
 use gb_core::hardware::boot_rom::{Bootrom, BootromData};
 use gb_core::hardware::color_palette::Color;
 use gb_core::hardware::Screen;
-use log::info;
+use gb_core::patch;
+use gb_core::rewind::Rewind;
+use gb_core::savestate;
+use gb_core::trace::{TraceLine, TraceSink};
+use log::{error, info, warn};
 use std::cell::{Cell, RefCell};
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{Receiver, SyncSender, TryRecvError};
 use std::sync::{mpsc, Arc, Mutex};
-use std::time::Instant;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub enum EmulatorKeyEvent {
     GbEvent(GbEvents),
     Save,
+    Load,
+    SelectSlot(u8),
+    Rewind(bool),
+    ToggleTrace,
+}
+
+const ROM_PATH: &str = "C:\\roms\\sml.gb";
+
+fn state_slot_path(slot: u8) -> PathBuf {
+    Path::new(ROM_PATH).with_extension(format!("ss{}", slot))
 }
-fn main() {
-    use std::io::Write;
 
+fn sav_path() -> PathBuf {
+    Path::new(ROM_PATH).with_extension("sav")
+}
+
+fn trace_path() -> PathBuf {
+    Path::new(ROM_PATH).with_extension("log")
+}
+
+// Writes the instruction trace in the gameboy-doctor format
+struct FileTrace(BufWriter<File>);
+
+impl TraceSink for FileTrace {
+    fn trace(&mut self, line: &TraceLine) {
+        let _ = writeln!(self.0, "{}", line);
+    }
+}
+
+fn write_sav(data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+    if let Err(err) = std::fs::write(sav_path(), data) {
+        error!("Could not write save file: {}", err);
+    }
+}
+
+// Applies `<rom>.ips`, `<rom>.ups` or `<rom>.bps` when one sits next to the ROM
+const AUTO_PATCH: bool = true;
+
+fn apply_patch(rom: Vec<u8>) -> Vec<u8> {
+    for format in patch::PatchFormat::ALL {
+        let path = Path::new(ROM_PATH).with_extension(format.extension());
+        let Ok(data) = fs::read(&path) else {
+            continue;
+        };
+        info!("APPLYING PATCH {}", path.display());
+        return match patch::apply(&rom, &data) {
+            Ok(patched) => patched,
+            Err(err) => {
+                error!("Could not apply patch: {}", err);
+                rom
+            }
+        };
+    }
+    rom
+}
+
+// Accepts GDB clients on this localhost port, e.g. Some(2159)
+const GDB_PORT: Option<u16> = None;
+
+// Roughly five seconds between save file flushes
+const SAV_FLUSH_FRAMES: u32 = 300;
+fn main() {
     let mut builder = env_logger::Builder::from_default_env();
 
     builder
@@ -38,7 +105,7 @@ fn main() {
 
 pub fn construct_cpu() {
     let mut gb_rom: Vec<u8> = vec![];
-    File::open("C:\\roms\\sml.gb")
+    File::open(ROM_PATH)
         .and_then(|mut f| f.read_to_end(&mut gb_rom))
         .map_err(|_| "Could not read ROM")
         .unwrap();
@@ -48,9 +115,22 @@ pub fn construct_cpu() {
     //     .map_err(|_| "Could not read ROM")
     //     .unwrap();
 
+    if AUTO_PATCH {
+        gb_rom = apply_patch(gb_rom);
+    }
+
     info!("STARTING");
     let gb_rom = ByteRomManager::new(gb_rom.into_boxed_slice());
-    let gb_rom = gb_core::hardware::rom::Rom::from_bytes(gb_rom);
+    let gb_rom = match gb_core::hardware::rom::Rom::from_bytes(gb_rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            error!("Could not load ROM: {}", err);
+            return;
+        }
+    };
+    for mismatch in gb_rom.header_mismatches() {
+        warn!("ROM header mismatch, the dump may be corrupted: {:?}", mismatch);
+    }
 
     //  let (do_save_sender, do_save_receiver) = mpsc::sync_channel::<bool>(1);
     let save_signal: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
@@ -63,6 +143,7 @@ pub fn construct_cpu() {
         sender: sender2,
         off_screen_buffer: RefCell::new(Box::new([0; SCREEN_PIXELS])),
         check: save_signal.clone(),
+        closed: false,
     };
 
     // let boot_room_stuff = Bootrom::new(Some(BootromData::from_bytes(include_bytes!(
@@ -78,10 +159,16 @@ pub fn construct_cpu() {
         let waitticks = (4194304f64 / 1000.0 * 16.0).round() as u32;
         let mut ticks = 0;
 
-        let cart = gb_rom.into_cartridge();
+        let header = gb_rom.header.clone();
+        let mut cart = gb_rom.into_cartridge();
+        cart.set_rtc_host_sync(true);
+        if let Ok(data) = std::fs::read(sav_path()) {
+            info!("LOADING SAVE FILE");
+            cart.load_ram(&data);
+        }
 
-        //  let state = fs::read_to_string("C:\\roms\\f_test2.state").unwrap();
-        // let gb_state = serde_json::from_str::<GameBoyState>(&state).unwrap(); //GameBoyState
+        // let mut state = File::open(state_slot_path(0)).unwrap();
+        // let gb_state = savestate::read_state(&mut state, &header).unwrap();
         let mut gameboy = GameBoy::create(
             sync_screen,
             cart,
@@ -98,20 +185,69 @@ pub fn construct_cpu() {
         //     gb_state,
         // );
 
+        // Twenty seconds of history, one snapshot every other frame
+        let mut rewind = Rewind::new(600, 2);
+        let mut rewinding = false;
+        let mut slot = 0;
+        let mut sav_frames = 0;
+        let mut last_sav = gameboy.save_ram();
+        let mut gdb = GDB_PORT.and_then(|port| match gb_gdb::GdbStub::bind(port) {
+            Ok(gdb) => Some(gdb),
+            Err(err) => {
+                error!("Could not start GDB stub: {}", err);
+                None
+            }
+        });
+
         'outer: loop {
-            while ticks < waitticks {
-                ticks += gameboy.tick() as u32
+            if rewinding {
+                if let Err(err) = rewind.rewind(&mut gameboy, 1) {
+                    error!("Could not rewind: {}", err);
+                }
+            }
+            if let Some(gdb) = gdb.as_mut() {
+                gdb.poll(&mut gameboy);
+            }
+            match gdb.as_mut().filter(|gdb| gdb.attached()) {
+                // Breakpoints are only checked while a client is attached
+                Some(gdb) => loop {
+                    match gameboy.run_to_frame() {
+                        DebugEvent::FrameCompleted => break,
+                        event => gdb.stopped(&mut gameboy, event),
+                    }
+                },
+                None => {
+                    while ticks < waitticks {
+                        ticks += gameboy.tick() as u32
+                    }
+                    ticks -= waitticks;
+                }
+            }
+            if gameboy.get_screen().closed {
+                break 'outer;
+            }
+            if !rewinding {
+                if let Err(err) = rewind.record_frame(&gameboy) {
+                    error!("Could not record rewind snapshot: {}", err);
+                }
             }
 
-            ticks -= waitticks;
+            sav_frames += 1;
+            if sav_frames >= SAV_FLUSH_FRAMES {
+                sav_frames = 0;
+                let sav = gameboy.save_ram();
+                if sav != last_sav {
+                    write_sav(&sav);
+                    last_sav = sav;
+                }
+            }
 
             let mut check = save_signal.lock().unwrap();
             if *(check) == true {
                 println!("SAVING");
                 let state = gameboy.create_state();
-                let string = serde_json::to_string(&state).unwrap();
                 let mut file = File::create("C:\\roms\\test_fail.state").unwrap();
-                file.write_all(string.as_bytes()).unwrap();
+                savestate::write_state(&mut file, &state, &header, true).unwrap();
                 *check = false;
             }
 
@@ -123,11 +259,53 @@ pub fn construct_cpu() {
                             GbEvents::KeyDown(key) => gameboy.key_pressed(key),
                         },
                         EmulatorKeyEvent::Save => {
-                            info!("SAVING STATE");
+                            info!("SAVING STATE TO SLOT {}", slot);
                             let state = gameboy.create_state();
-                            let string = serde_json::to_string(&state).unwrap();
-                            let mut file = File::create("C:\\roms\\f_test2.state").unwrap();
-                            file.write_all(string.as_bytes()).unwrap();
+                            let result = File::create(state_slot_path(slot))
+                                .map_err(|_| savestate::SaveStateError::Io)
+                                .and_then(|mut file| {
+                                    savestate::write_state(&mut file, &state, &header, true)
+                                });
+                            if let Err(err) = result {
+                                error!("Could not save state: {}", err);
+                            }
+                        }
+                        EmulatorKeyEvent::Load => {
+                            info!("LOADING STATE FROM SLOT {}", slot);
+                            let result = File::open(state_slot_path(slot))
+                                .map_err(|_| savestate::SaveStateError::Io)
+                                .and_then(|mut file| savestate::read_state(&mut file, &header));
+                            match result {
+                                Ok(state) => {
+                                    gameboy.load_state(state);
+                                    rewind.clear();
+                                }
+                                Err(err) => error!("Could not load state: {}", err),
+                            }
+                        }
+                        EmulatorKeyEvent::SelectSlot(selected) => {
+                            info!("STATE SLOT {}", selected);
+                            slot = selected;
+                        }
+                        EmulatorKeyEvent::Rewind(active) => rewinding = active,
+                        EmulatorKeyEvent::ToggleTrace => {
+                            if gb_core::is_log_enabled() {
+                                info!("STOPPING TRACE");
+                                gb_core::disable_logging();
+                                gameboy.detach_trace();
+                                gameboy.set_doctor_ly(false);
+                            } else {
+                                match File::create(trace_path()) {
+                                    Ok(file) => {
+                                        info!("TRACING TO {}", trace_path().display());
+                                        let sink = FileTrace(BufWriter::new(file));
+                                        gameboy.attach_trace(Box::new(sink));
+                                        gameboy.set_doctor_ly(true);
+                                        gb_core::enable_logging();
+                                    }
+                                    Err(err) => error!("Could not create trace file: {}", err),
+                                }
+                            }
                         }
                     },
                     Err(TryRecvError::Empty) => break 'recv,
@@ -138,6 +316,7 @@ pub fn construct_cpu() {
                 let _ = periodic.recv();
             }
         }
+        write_sav(&gameboy.save_ram());
     });
 
     render(gl_screen, control_sender);
@@ -159,6 +338,8 @@ pub struct SynScreen {
     sender: SyncSender<Box<[u8; SCREEN_PIXELS]>>,
     off_screen_buffer: RefCell<Box<[u8; SCREEN_PIXELS]>>,
     check: Arc<Mutex<bool>>,
+    // Set once the window is gone so the emulator loop can flush the save and stop
+    closed: bool,
 }
 
 impl Screen for SynScreen {
@@ -177,7 +358,9 @@ impl Screen for SynScreen {
 
     fn draw(&mut self, skip: bool) {
         let stuff = self.off_screen_buffer.replace(Box::new([0; SCREEN_PIXELS]));
-        self.sender.send(stuff).unwrap();
+        if self.sender.send(stuff).is_err() {
+            self.closed = true;
+        }
     }
 
     fn frame_rate(&self) -> u8 {
@@ -187,27 +370,29 @@ impl Screen for SynScreen {
 
 struct ByteRomManager {
     data: Box<[u8]>,
-    instant: Instant,
 }
 
 impl ByteRomManager {
     fn new(data: Box<[u8]>) -> Self {
-        return Self {
-            data,
-            instant: Instant::now(),
-        };
+        return Self { data };
     }
 }
 
 impl gb_core::hardware::rom::RomManager for ByteRomManager {
-    fn read_from_offset(&self, seek_offset: usize, index: usize, _bank_number: u8) -> u8 {
+    fn read_from_offset(&self, seek_offset: usize, index: usize, _bank_number: u16) -> u8 {
         let address = seek_offset + index;
         let result = self.data[address];
         result
     }
 
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
     fn clock(&self) -> u64 {
-        self.instant.elapsed().as_micros() as u64
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_micros() as u64)
     }
 
     fn save(&mut self, _game_title: &str, bank_index: u8, _bank: &[u8]) {
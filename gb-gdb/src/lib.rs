@@ -0,0 +1,512 @@
+//! GDB remote serial protocol stub.
+//!
+//! Registers are exposed like GDB's z80 target: AF, BC, DE, HL, SP and PC as 16 bit little
+//! endian values. Memory goes through the bus as the CPU sees it, writes included, so
+//! writing to ROM switches banks. Connect with `target remote localhost:<port>`.
+//!
+//! The stub has no window or threads of its own, frontends call [`GdbStub::poll`] once per
+//! frame and hand it the events of [`GameBoy::run_to_frame`] while a client is attached.
+
+use gb_core::debugger::{Access, Breakpoint, DebugEvent, Watchpoint};
+use gb_core::gameboy::GameBoy;
+use gb_core::hardware::Screen;
+use log::{info, warn};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const REGISTER_COUNT: usize = 6;
+
+// GDB signal numbers used in stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    last_stop: String,
+}
+
+enum Command {
+    Reply(String),
+    Continue,
+    Step,
+    Detach,
+}
+
+impl GdbStub {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            client: None,
+            last_stop: stop_reply(SIGTRAP),
+        })
+    }
+
+    pub fn attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Accepts a client or checks the attached one for an interrupt, once per frame. Blocks
+    /// while the client keeps the emulator stopped.
+    pub fn poll<S: Screen>(&mut self, gameboy: &mut GameBoy<S>) {
+        let Some(stream) = &mut self.client else {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    info!("GDB CLIENT CONNECTED FROM {}", address);
+                    self.client = Some(stream);
+                    // The client expects the target to be stopped when it attaches
+                    self.last_stop = stop_reply(SIGTRAP);
+                    self.serve(gameboy, false);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => warn!("Could not accept GDB client: {}", err),
+            }
+            return;
+        };
+        match interrupted(stream) {
+            Ok(false) => {}
+            Ok(true) => {
+                self.last_stop = stop_reply(SIGINT);
+                self.serve(gameboy, true);
+            }
+            Err(err) => self.disconnect(gameboy, err),
+        }
+    }
+
+    /// Reports a breakpoint or watchpoint hit and hands control to the client.
+    pub fn stopped<S: Screen>(&mut self, gameboy: &mut GameBoy<S>, event: DebugEvent) {
+        self.last_stop = event_reply(event);
+        self.serve(gameboy, true);
+    }
+
+    fn serve<S: Screen>(&mut self, gameboy: &mut GameBoy<S>, notify: bool) {
+        match self.command_loop(gameboy, notify) {
+            Ok(true) => {}
+            Ok(false) => self.disconnect(gameboy, ErrorKind::ConnectionAborted.into()),
+            Err(err) => self.disconnect(gameboy, err),
+        }
+    }
+
+    /// Handles packets until the client resumes the emulator, `false` when it detached.
+    fn command_loop<S: Screen>(
+        &mut self,
+        gameboy: &mut GameBoy<S>,
+        notify: bool,
+    ) -> io::Result<bool> {
+        let Some(stream) = &mut self.client else {
+            return Ok(false);
+        };
+        stream.set_nonblocking(false)?;
+        if notify {
+            send_packet(stream, &self.last_stop)?;
+        }
+        loop {
+            let packet = read_packet(stream)?;
+            match handle(gameboy, &packet, &self.last_stop) {
+                Command::Reply(reply) => send_packet(stream, &reply)?,
+                Command::Continue => break,
+                Command::Step => {
+                    self.last_stop = event_reply(gameboy.step_into());
+                    send_packet(stream, &self.last_stop)?;
+                }
+                Command::Detach => {
+                    send_packet(stream, "OK")?;
+                    return Ok(false);
+                }
+            }
+        }
+        stream.set_nonblocking(true)?;
+        Ok(true)
+    }
+
+    fn disconnect<S: Screen>(&mut self, gameboy: &mut GameBoy<S>, err: io::Error) {
+        info!("GDB CLIENT DISCONNECTED: {}", err);
+        self.client = None;
+        gameboy.debugger().clear();
+    }
+}
+
+fn handle<S: Screen>(gameboy: &mut GameBoy<S>, packet: &str, last_stop: &str) -> Command {
+    let mut characters = packet.chars();
+    let kind = characters.next().unwrap_or_default();
+    let arguments = characters.as_str();
+    let reply = match kind {
+        '?' => Some(last_stop.to_string()),
+        'g' => Some(
+            (0..REGISTER_COUNT)
+                .map(|register| encode_word(read_register(gameboy, register)))
+                .collect(),
+        ),
+        'G' => write_registers(gameboy, arguments),
+        'p' => usize::from_str_radix(arguments, 16)
+            .ok()
+            .filter(|register| *register < REGISTER_COUNT)
+            .map(|register| encode_word(read_register(gameboy, register))),
+        'P' => arguments.split_once('=').and_then(|(register, value)| {
+            let register = usize::from_str_radix(register, 16).ok()?;
+            write_register(gameboy, register, decode_word(value)?)?;
+            Some("OK".to_string())
+        }),
+        'm' => parse_range(arguments).map(|(address, length)| {
+            (0..length)
+                .map(|offset| format!("{:02x}", gameboy.peek(address.wrapping_add(offset))))
+                .collect()
+        }),
+        'M' => write_memory(gameboy, arguments),
+        'Z' | 'z' => set_breakpoint(gameboy, arguments, kind == 'Z'),
+        'c' | 's' => {
+            // Resuming from another address, GDB does not use it but the protocol allows it
+            if let Ok(address) = u16::from_str_radix(arguments, 16) {
+                gameboy.cpu.registers.pc = address;
+            }
+            return if kind == 'c' {
+                Command::Continue
+            } else {
+                Command::Step
+            };
+        }
+        'D' | 'k' => return Command::Detach,
+        'H' => Some("OK".to_string()),
+        'q' if arguments.starts_with("Supported") => Some("PacketSize=1000".to_string()),
+        'q' if arguments == "Attached" => Some("1".to_string()),
+        // Anything else is unsupported, which an empty reply tells the client
+        _ => Some(String::new()),
+    };
+    Command::Reply(reply.unwrap_or_else(|| "E01".to_string()))
+}
+
+fn read_register<S: Screen>(gameboy: &GameBoy<S>, register: usize) -> u16 {
+    let registers = &gameboy.cpu.registers;
+    match register {
+        0 => registers.get_af(),
+        1 => registers.get_bc(),
+        2 => registers.get_de(),
+        3 => registers.get_hl(),
+        4 => registers.sp,
+        _ => registers.pc,
+    }
+}
+
+fn write_register<S: Screen>(gameboy: &mut GameBoy<S>, register: usize, value: u16) -> Option<()> {
+    let registers = &mut gameboy.cpu.registers;
+    match register {
+        0 => registers.set_af(value),
+        1 => registers.set_bc(value),
+        2 => registers.set_de(value),
+        3 => registers.set_hl(value),
+        4 => registers.sp = value,
+        5 => registers.pc = value,
+        _ => return None,
+    }
+    Some(())
+}
+
+fn write_registers<S: Screen>(gameboy: &mut GameBoy<S>, arguments: &str) -> Option<String> {
+    if arguments.len() < REGISTER_COUNT * 4 {
+        return None;
+    }
+    let values = (0..REGISTER_COUNT)
+        .map(|register| decode_word(arguments.get(register * 4..register * 4 + 4)?))
+        .collect::<Option<Vec<_>>>()?;
+    for (register, value) in values.into_iter().enumerate() {
+        write_register(gameboy, register, value)?;
+    }
+    Some("OK".to_string())
+}
+
+fn write_memory<S: Screen>(gameboy: &mut GameBoy<S>, arguments: &str) -> Option<String> {
+    let (range, data) = arguments.split_once(':')?;
+    let (address, length) = parse_range(range)?;
+    if data.len() != length as usize * 2 {
+        return None;
+    }
+    let bytes = (0..length as usize)
+        .map(|index| u8::from_str_radix(data.get(index * 2..index * 2 + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    for (offset, value) in bytes.into_iter().enumerate() {
+        gameboy.poke(address.wrapping_add(offset as u16), value);
+    }
+    Some("OK".to_string())
+}
+
+/// `Z<type>,<address>,<kind>`: 0 and 1 are breakpoints, 2 to 4 write, read and access
+/// watchpoints.
+fn set_breakpoint<S: Screen>(
+    gameboy: &mut GameBoy<S>,
+    arguments: &str,
+    insert: bool,
+) -> Option<String> {
+    let mut fields = arguments.split(',');
+    let kind = fields.next()?;
+    let address = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let length = fields
+        .next()
+        .and_then(|length| u16::from_str_radix(length, 16).ok())
+        .unwrap_or(1)
+        .max(1);
+    let access = match kind {
+        "0" | "1" => None,
+        "2" => Some(Access::WRITE),
+        "3" => Some(Access::READ),
+        "4" => Some(Access::READ | Access::WRITE),
+        _ => return Some(String::new()),
+    };
+    let debugger = gameboy.debugger();
+    match access {
+        None if insert => {
+            debugger.add_breakpoint(Breakpoint::new(address));
+        }
+        None => {
+            let index = debugger
+                .breakpoints()
+                .iter()
+                .position(|breakpoint| breakpoint.address == address)?;
+            debugger.remove_breakpoint(index);
+        }
+        Some(access) => {
+            let watchpoint = Watchpoint::new(address, address.saturating_add(length - 1), access);
+            if insert {
+                debugger.add_watchpoint(watchpoint);
+            } else {
+                let index = debugger
+                    .watchpoints()
+                    .iter()
+                    .position(|existing| *existing == watchpoint)?;
+                debugger.remove_watchpoint(index);
+            }
+        }
+    }
+    Some("OK".to_string())
+}
+
+/// `<address>,<length>` in hexadecimal.
+fn parse_range(arguments: &str) -> Option<(u16, u16)> {
+    let (address, length) = arguments.split_once(',')?;
+    Some((
+        u16::from_str_radix(address, 16).ok()?,
+        u16::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+fn encode_word(value: u16) -> String {
+    format!("{:02x}{:02x}", value as u8, (value >> 8) as u8)
+}
+
+fn decode_word(value: &str) -> Option<u16> {
+    let low = u8::from_str_radix(value.get(0..2)?, 16).ok()?;
+    let high = u8::from_str_radix(value.get(2..4)?, 16).ok()?;
+    Some(u16::from_le_bytes([low, high]))
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn event_reply(event: DebugEvent) -> String {
+    match event {
+        DebugEvent::Watchpoint {
+            address, access, ..
+        } if access != Access::EXECUTE => {
+            let kind = if access == Access::WRITE {
+                "watch"
+            } else {
+                "rwatch"
+            };
+            format!("T{:02x}{}:{:04x};", SIGTRAP, kind, address)
+        }
+        _ => stop_reply(SIGTRAP),
+    }
+}
+
+/// Checks a running client for the interrupt byte GDB sends on Ctrl-C.
+fn interrupted(stream: &mut TcpStream) -> io::Result<bool> {
+    let mut buffer = [0; 64];
+    match stream.read(&mut buffer) {
+        Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
+        Ok(read) => Ok(buffer[..read].contains(&0x03)),
+        Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<u8> {
+    let mut byte = [0];
+    stream.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Reads the next `$<data>#<checksum>` packet and acknowledges it, a bad checksum asks the
+/// client to send it again.
+fn read_packet(stream: &mut TcpStream) -> io::Result<String> {
+    loop {
+        // Acknowledgements and interrupts while stopped are skipped
+        while read_byte(stream)? != b'$' {}
+        let mut data = Vec::new();
+        loop {
+            match read_byte(stream)? {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let checksum = [read_byte(stream)?, read_byte(stream)?];
+        let expected = std::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+        if expected == Some(checksum_of(&data)) {
+            stream.write_all(b"+")?;
+            return Ok(String::from_utf8_lossy(&data).into_owned());
+        }
+        stream.write_all(b"-")?;
+    }
+}
+
+fn send_packet(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+    stream.write_all(packet.as_bytes())?;
+    // The acknowledgement is skipped by the next read
+    stream.flush()
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gb_core::hardware::boot_rom::Bootrom;
+    use gb_core::hardware::color_palette::Color;
+    use gb_core::hardware::rom::{Rom, RomManager};
+    use gb_core::hardware::sound::AudioPlayer;
+    use std::ops::{Index, Range};
+    use std::thread;
+
+    struct TestRom(Vec<u8>);
+
+    impl Index<usize> for TestRom {
+        type Output = u8;
+
+        fn index(&self, index: usize) -> &Self::Output {
+            &self.0[index]
+        }
+    }
+
+    impl Index<Range<usize>> for TestRom {
+        type Output = [u8];
+
+        fn index(&self, index: Range<usize>) -> &Self::Output {
+            &self.0[index]
+        }
+    }
+
+    impl RomManager for TestRom {
+        fn read_from_offset(&self, seek_offset: usize, index: usize, _bank_number: u16) -> u8 {
+            self.0[seek_offset + index]
+        }
+
+        fn size(&self) -> usize {
+            self.0.len()
+        }
+
+        fn clock(&self) -> u64 {
+            0
+        }
+
+        fn save(&mut self, _game_title: &str, _bank_index: u8, _bank: &[u8]) {}
+
+        fn load_to_bank(&mut self, _game_title: &str, _bank_index: u8, _bank: &mut [u8]) {}
+    }
+
+    struct NullScreen;
+
+    impl Screen for NullScreen {
+        fn turn_on(&mut self) {}
+
+        fn turn_off(&mut self) {}
+
+        fn set_pixel(&mut self, _x: u8, _y: u8, _color: Color) {}
+
+        fn draw(&mut self, _skip_next: bool) {}
+
+        fn frame_rate(&self) -> u8 {
+            60
+        }
+    }
+
+    struct NullPlayer;
+
+    impl AudioPlayer for NullPlayer {
+        fn play(&mut self, _output_buffer: &[u16]) {}
+
+        fn samples_rate(&self) -> u32 {
+            44100
+        }
+
+        fn underflowed(&self) -> bool {
+            false
+        }
+    }
+
+    /// A ROM only cartridge that jumps from the entry point to a loop at 0x150.
+    fn gameboy() -> GameBoy<'static, NullScreen> {
+        let mut data = vec![0; 0x8000];
+        // NOP; JP $0150
+        data[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        // JR @
+        data[0x150..0x152].copy_from_slice(&[0x18, 0xFE]);
+        let cartridge = Rom::from_bytes(TestRom(data)).unwrap().into_cartridge();
+        GameBoy::create(
+            NullScreen,
+            cartridge,
+            Bootrom::new(None),
+            Box::new(NullPlayer),
+        )
+    }
+
+    /// Sends a packet like GDB and returns the reply.
+    fn exchange(stream: &mut TcpStream, packet: &str) -> String {
+        send_packet(stream, packet).unwrap();
+        read_packet(stream).unwrap()
+    }
+
+    #[test]
+    fn serves_a_client_over_tcp() {
+        let mut gameboy = gameboy();
+        let mut stub = GdbStub::bind(0).unwrap();
+        let port = stub.listener.local_addr().unwrap().port();
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            assert_eq!(exchange(&mut stream, "?"), "S05");
+            // AF, BC, DE, HL, SP then PC at the entry point
+            let registers = exchange(&mut stream, "g");
+            assert_eq!(registers.len(), REGISTER_COUNT * 4);
+            assert_eq!(&registers[16..], "feff0001");
+            assert_eq!(exchange(&mut stream, "m0100,4"), "00c35001");
+            assert_eq!(exchange(&mut stream, "Z0,150,1"), "OK");
+            assert_eq!(exchange(&mut stream, "s"), "S05");
+            assert_eq!(exchange(&mut stream, "p5"), "0101");
+            // A multibyte command is unsupported, not fatal
+            assert_eq!(exchange(&mut stream, "é"), "");
+            send_packet(&mut stream, "c").unwrap();
+            // The breakpoint at the loop
+            assert_eq!(read_packet(&mut stream).unwrap(), "S05");
+            assert_eq!(exchange(&mut stream, "p5"), "5001");
+            assert_eq!(exchange(&mut stream, "D"), "OK");
+        });
+
+        while !stub.attached() {
+            stub.poll(&mut gameboy);
+        }
+        let event = gameboy.run_to_frame();
+        assert_eq!(event, DebugEvent::Breakpoint(0));
+        stub.stopped(&mut gameboy, event);
+        client.join().unwrap();
+
+        assert!(!stub.attached());
+        assert!(gameboy.debugger().breakpoints().is_empty());
+        assert_eq!(gameboy.cpu.registers.pc, 0x150);
+    }
+}